@@ -0,0 +1,55 @@
+//! Compares optimistic reads against locked reads of a shared counter.
+//!
+//! Run with `cargo bench --bench optimistic_read`.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use aloxtalk::memory::{Strong, Weak};
+
+const READS: usize = 1_000_000;
+const SAMPLES: usize = 5;
+
+fn run(s: &Strong<u64>, threads: usize, optimistic: bool) -> Duration {
+    let start = Instant::now();
+    let workers = (0..threads)
+        .map(|_| {
+            let shared = s.alias().share();
+            thread::spawn(move || {
+                let w = Weak::from(shared);
+                let mut sum = 0;
+                for _ in 0..READS {
+                    sum += match optimistic {
+                        true => w.try_read_copy().unwrap(),
+                        false => *w.read_blocking().unwrap(),
+                    };
+                }
+                sum
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), 42 * READS as u64);
+    }
+    start.elapsed() / (threads * READS) as u32
+}
+
+fn main() {
+    let s = Strong::new(42u64).make_sharable();
+    for threads in [1, 2, 4, 8] {
+        for optimistic in [false, true] {
+            let mut samples = (0..SAMPLES)
+                .map(|_| run(&s, threads, optimistic))
+                .collect::<Vec<_>>();
+            samples.sort();
+            let mode = if optimistic { "optimistic" } else { "locked" };
+            println!(
+                "{threads} threads, {mode:>10}: median {:?}, min {:?} per read",
+                samples[SAMPLES / 2],
+                samples[0]
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use crate::object::Symbol;
+use crate::syntax::{Block, ClassDefinition, Literal, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    PushLiteral(usize),
+    PushSelf,
+    PushTemp(usize, usize),
+    MoveTemp(usize, usize),
+    StoreTemp(usize, usize),
+    PushField(usize),
+    StoreField(usize),
+    PushGlobal(usize),
+    StoreGlobal(usize),
+    Send(usize, usize),
+    SuperSend(usize, usize),
+    MakeBlock(usize),
+    MakeArray(usize),
+    DefineClass(usize),
+    Dup,
+    Pop,
+    Cascade,
+    Jump(usize),
+    JumpIf(bool, usize),
+    Branch(usize),
+    Return,
+    ReturnTop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Branch {
+    pub(crate) selector: usize,
+    pub(crate) blocks: Vec<usize>,
+    pub(crate) otherwise: usize,
+    pub(crate) end: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Code {
+    pub(crate) names: Vec<Symbol>,
+    pub(crate) ops: Vec<Op>,
+    pub(crate) spans: Vec<Option<Span>>,
+    pub(crate) literals: Vec<Literal>,
+    pub(crate) symbols: Vec<Symbol>,
+    pub(crate) blocks: Vec<(Arc<Block>, Arc<Code>)>,
+    pub(crate) branches: Vec<Branch>,
+    pub(crate) classes: Vec<Arc<ClassDefinition>>,
+}
+
+impl Code {
+    pub(crate) fn emit(&mut self, op: Op, span: Option<Span>) -> usize {
+        self.ops.push(op);
+        self.spans.push(span);
+        self.ops.len() - 1
+    }
+
+    pub(crate) fn literal(&mut self, literal: &Literal) -> usize {
+        match self.literals.iter().position(|l| l == literal) {
+            Some(index) => index,
+            None => {
+                self.literals.push(literal.clone());
+                self.literals.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn symbol(&mut self, symbol: Symbol) -> usize {
+        match self.symbols.iter().position(|&s| s == symbol) {
+            Some(index) => index,
+            None => {
+                self.symbols.push(symbol);
+                self.symbols.len() - 1
+            }
+        }
+    }
+}
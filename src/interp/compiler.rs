@@ -0,0 +1,291 @@
+use std::sync::Arc;
+
+use super::bytecode::{Branch, Code, Op};
+use crate::object::{intern, Symbol};
+use crate::syntax::{Block, Body, Expr, ExprKind, Literal, Message, MethodDefinition, Statement};
+
+struct Scope<'a> {
+    names: Vec<Symbol>,
+    outer: Option<&'a Scope<'a>>,
+}
+
+enum Variable {
+    Temp(usize, usize),
+    Field(usize),
+    Global,
+}
+
+pub(crate) struct Compiler<'a> {
+    scope: Scope<'a>,
+    fields: &'static [Symbol],
+    code: Code,
+}
+
+pub(crate) fn compile_body(body: &Body) -> Code {
+    let names = body.temporaries.iter().map(|t| intern(t)).collect();
+    let mut compiler = Compiler::new(names, None, &[]);
+    compiler.body(body);
+    compiler.finish()
+}
+
+pub(crate) fn compile_method(method: &MethodDefinition, fields: &'static [Symbol]) -> Code {
+    let names = method
+        .parameters
+        .iter()
+        .chain(&method.body.temporaries)
+        .map(|name| intern(name))
+        .collect();
+    let mut compiler = Compiler::new(names, None, fields);
+    compiler.body(&method.body);
+    compiler.finish()
+}
+
+impl<'a> Compiler<'a> {
+    fn new(names: Vec<Symbol>, outer: Option<&'a Scope<'a>>, fields: &'static [Symbol]) -> Self {
+        Self {
+            scope: Scope { names, outer },
+            fields,
+            code: Code::default(),
+        }
+    }
+
+    fn finish(mut self) -> Code {
+        self.code.emit(Op::ReturnTop, None);
+        self.code.names = self.scope.names;
+        self.code
+    }
+
+    fn resolve(&self, name: &str) -> Variable {
+        let mut depth = 0;
+        let mut scope = Some(&self.scope);
+        while let Some(s) = scope {
+            if let Some(index) = s.names.iter().rposition(|&n| n == name) {
+                return Variable::Temp(depth, index);
+            }
+            depth += 1;
+            scope = s.outer;
+        }
+        match self.fields.iter().rposition(|&n| n == name) {
+            Some(index) => Variable::Field(index),
+            None => Variable::Global,
+        }
+    }
+
+    fn body(&mut self, body: &Body) {
+        if body.statements.is_empty() {
+            self.nil();
+        }
+        for (i, statement) in body.statements.iter().enumerate() {
+            match statement {
+                Statement::Expression(expr) => self.expr(expr),
+                Statement::Return(expr, _) => {
+                    self.expr(expr);
+                    self.code.emit(Op::Return, Some(expr.span));
+                    return;
+                }
+            }
+            if i + 1 < body.statements.len() {
+                self.code.emit(Op::Pop, None);
+            }
+        }
+    }
+
+    fn nil(&mut self) {
+        let index = self.code.literal(&Literal::Nil);
+        self.code.emit(Op::PushLiteral(index), None);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        let span = Some(expr.span);
+        match &expr.kind {
+            ExprKind::Literal(literal) => {
+                let index = self.code.literal(literal);
+                self.code.emit(Op::PushLiteral(index), span);
+            }
+            ExprKind::Variable(name) if name == "self" || name == "super" => {
+                self.code.emit(Op::PushSelf, span);
+            }
+            ExprKind::Variable(name) => {
+                let op = match self.resolve(name) {
+                    Variable::Temp(depth, index) => Op::PushTemp(depth, index),
+                    Variable::Field(index) => Op::PushField(index),
+                    Variable::Global => Op::PushGlobal(self.code.symbol(intern(name))),
+                };
+                self.code.emit(op, span);
+            }
+            ExprKind::Move(name) => {
+                let op = match self.resolve(name) {
+                    Variable::Temp(depth, index) => Op::MoveTemp(depth, index),
+                    Variable::Field(index) => Op::PushField(index),
+                    Variable::Global => Op::PushGlobal(self.code.symbol(intern(name))),
+                };
+                self.code.emit(op, span);
+            }
+            ExprKind::Assign(name, value) => {
+                self.expr(value);
+                let op = match self.resolve(name) {
+                    Variable::Temp(depth, index) => Op::StoreTemp(depth, index),
+                    Variable::Field(index) => Op::StoreField(index),
+                    Variable::Global => Op::StoreGlobal(self.code.symbol(intern(name))),
+                };
+                self.code.emit(op, span);
+            }
+            ExprKind::Send(receiver, message)
+                if receiver.kind == ExprKind::Variable("super".into()) =>
+            {
+                self.code.emit(Op::PushSelf, Some(receiver.span));
+                self.arguments(message);
+                let selector = self.code.symbol(intern(&message.selector));
+                let op = Op::SuperSend(selector, message.arguments.len());
+                self.code.emit(op, Some(message.span));
+            }
+            ExprKind::Send(receiver, message) => {
+                if !self.inline(receiver, message) {
+                    self.expr(receiver);
+                    self.send(message);
+                }
+            }
+            ExprKind::Cascade(receiver, messages) => {
+                self.expr(receiver);
+                for (i, message) in messages.iter().enumerate() {
+                    self.code.emit(Op::Dup, None);
+                    self.send(message);
+                    if i + 1 < messages.len() {
+                        self.code.emit(Op::Pop, None);
+                    }
+                }
+                self.code.emit(Op::Cascade, None);
+            }
+            ExprKind::Block(block) => {
+                let index = self.block(block);
+                self.code.emit(Op::MakeBlock(index), None);
+            }
+            ExprKind::Brace(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+                self.code.emit(Op::MakeArray(items.len()), None);
+            }
+            ExprKind::Class(definition) => {
+                self.code.classes.push(definition.clone());
+                let index = self.code.classes.len() - 1;
+                self.code.emit(Op::DefineClass(index), span);
+            }
+        }
+    }
+
+    fn arguments(&mut self, message: &Message) {
+        for argument in &message.arguments {
+            self.expr(argument);
+        }
+    }
+
+    fn send(&mut self, message: &Message) {
+        self.arguments(message);
+        let selector = self.code.symbol(intern(&message.selector));
+        let op = Op::Send(selector, message.arguments.len());
+        self.code.emit(op, Some(message.span));
+    }
+
+    fn block(&mut self, block: &Arc<Block>) -> usize {
+        let names = block
+            .parameters
+            .iter()
+            .chain(&block.body.temporaries)
+            .map(|name| intern(name))
+            .collect();
+        let mut compiler = Compiler::new(names, Some(&self.scope), self.fields);
+        compiler.body(&block.body);
+        let code = compiler.finish();
+        self.code.blocks.push((block.clone(), Arc::new(code)));
+        self.code.blocks.len() - 1
+    }
+
+    fn inline(&mut self, receiver: &Expr, message: &Message) -> bool {
+        let Some(arms) = message
+            .arguments
+            .iter()
+            .map(inlinable)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        match message.selector.as_str() {
+            "ifTrue:" => self.conditional(receiver, message, Some(arms[0]), None),
+            "ifFalse:" => self.conditional(receiver, message, None, Some(arms[0])),
+            "ifTrue:ifFalse:" => self.conditional(receiver, message, Some(arms[0]), Some(arms[1])),
+            "ifFalse:ifTrue:" => self.conditional(receiver, message, Some(arms[1]), Some(arms[0])),
+            "whileTrue:" | "whileFalse:" => match inlinable(receiver) {
+                Some(condition) => {
+                    let exit = message.selector == "whileFalse:";
+                    self.loop_while(condition, arms[0], exit, message);
+                }
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn conditional(
+        &mut self,
+        receiver: &Expr,
+        message: &Message,
+        then: Option<&Arc<Block>>,
+        otherwise: Option<&Arc<Block>>,
+    ) {
+        self.expr(receiver);
+        let blocks = message
+            .arguments
+            .iter()
+            .filter_map(inlinable)
+            .map(|block| self.block(block))
+            .collect();
+        let selector = self.code.symbol(intern(&message.selector));
+        self.code.branches.push(Branch {
+            selector,
+            blocks,
+            otherwise: 0,
+            end: 0,
+        });
+        let branch = self.code.branches.len() - 1;
+        self.code.emit(Op::Branch(branch), Some(message.span));
+
+        self.arm(then);
+        let jump = self.code.emit(Op::Jump(0), None);
+        self.code.branches[branch].otherwise = self.code.ops.len();
+        self.arm(otherwise);
+        let end = self.code.ops.len();
+        self.code.ops[jump] = Op::Jump(end);
+        self.code.branches[branch].end = end;
+    }
+
+    fn loop_while(&mut self, condition: &Block, body: &Block, exit: bool, message: &Message) {
+        let start = self.code.ops.len();
+        self.body(&condition.body);
+        let test = self.code.emit(Op::JumpIf(exit, 0), Some(message.span));
+        self.body(&body.body);
+        self.code.emit(Op::Pop, None);
+        self.code.emit(Op::Jump(start), None);
+        self.code.ops[test] = Op::JumpIf(exit, self.code.ops.len());
+        self.nil();
+    }
+
+    fn arm(&mut self, block: Option<&Arc<Block>>) {
+        match block {
+            Some(block) => self.body(&block.body),
+            None => self.nil(),
+        }
+    }
+}
+
+pub(crate) fn inlinable(expr: &Expr) -> Option<&Arc<Block>> {
+    match &expr.kind {
+        ExprKind::Block(block)
+            if block.parameters.is_empty() && block.body.temporaries.is_empty() =>
+        {
+            Some(block)
+        }
+        _ => None,
+    }
+}
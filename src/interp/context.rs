@@ -0,0 +1,65 @@
+use std::mem;
+
+use super::{identical, store};
+use crate::memory::Weak;
+use crate::object::{slots::Slot, Class, Symbol};
+
+pub(crate) struct Context {
+    names: Vec<Symbol>,
+    values: Vec<Slot>,
+    pub(crate) outer: Option<Weak<Context>>,
+    pub(crate) receiver: Option<Slot>,
+    pub(crate) method_class: Option<&'static Class>,
+    pub(crate) home: usize,
+}
+
+impl Context {
+    pub(crate) fn new(
+        names: Vec<Symbol>,
+        mut values: Vec<Slot>,
+        outer: Option<Weak<Context>>,
+        home: usize,
+    ) -> Self {
+        values.resize_with(names.len(), Slot::nil);
+        Self {
+            names,
+            values,
+            outer,
+            receiver: None,
+            method_class: None,
+            home,
+        }
+    }
+
+    pub(crate) fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().rposition(|&n| n == name)
+    }
+
+    pub(crate) fn get(&self, index: usize) -> &Slot {
+        &self.values[index]
+    }
+
+    pub(crate) fn set(&mut self, index: usize, value: Slot) -> Slot {
+        store(&mut self.values[index], value)
+    }
+
+    pub(crate) fn take(&mut self, index: usize) -> Slot {
+        let alias = self.values[index].alias();
+        match self.values[index].is_strong() {
+            true => mem::replace(&mut self.values[index], alias),
+            false => alias,
+        }
+    }
+
+    pub(crate) fn rescue(&mut self, value: Slot) -> Slot {
+        if value.object().is_none() || value.is_strong() {
+            return value;
+        }
+        for slot in self.values.iter_mut().chain(self.receiver.iter_mut()) {
+            if slot.is_strong() && identical(slot, &value) {
+                return mem::replace(slot, Slot::nil());
+            }
+        }
+        value
+    }
+}
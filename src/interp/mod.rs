@@ -0,0 +1,724 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::{fmt, mem};
+
+use self::bytecode::Code;
+use crate::memory::{AccessError, Reading, Strong, Weak};
+use crate::object::class::{BUILTIN_CLASSES, SMALL_INTEGER, SYSTEM_DICTIONARY, UNDEFINED_OBJECT};
+use crate::object::{intern, slots::Slot, Class, Method, Object, Procedure};
+use crate::syntax::{
+    Body, ClassDefinition, Expr, ExprKind, Literal, Message, MethodDefinition, Span, Statement,
+};
+
+pub(crate) mod bytecode;
+pub(crate) mod compiler;
+pub(crate) mod context;
+pub(crate) mod ownership;
+pub(crate) mod primitives;
+mod tests;
+mod vm;
+
+pub(crate) use context::Context;
+pub use ownership::{infer_body, Diagnostic};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl RuntimeError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: None,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Tree,
+    Bytecode,
+}
+
+pub(crate) enum Unwind {
+    Error(RuntimeError),
+    Return(Slot, usize),
+}
+
+impl Unwind {
+    fn at(self, span: Span) -> Self {
+        match self {
+            Unwind::Error(RuntimeError {
+                message,
+                span: None,
+            }) => Unwind::Error(RuntimeError {
+                message,
+                span: Some(span),
+            }),
+            other => other,
+        }
+    }
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(it: RuntimeError) -> Self {
+        Unwind::Error(it)
+    }
+}
+
+pub(crate) type Outcome = Result<Slot, Unwind>;
+
+pub(crate) fn error<T>(message: impl Into<String>) -> Result<T, Unwind> {
+    Err(RuntimeError::new(message).into())
+}
+
+pub(crate) fn context_error<T>(err: AccessError) -> Result<T, Unwind> {
+    match err {
+        AccessError::Dangling { .. } => error("reference to a dead context"),
+        err => error(format!("cannot access context, {err}")),
+    }
+}
+
+pub(crate) fn identical(a: &Slot, b: &Slot) -> bool {
+    match (a.object(), b.object()) {
+        (Some(a), Some(b)) => a.ptr_eq(&b),
+        (None, None) => a.as_int() == b.as_int(),
+        _ => false,
+    }
+}
+
+// Storing an alias over the slot that owns its object would free the object,
+// as when a local that already moved there is stored again.
+pub(crate) fn store(slot: &mut Slot, value: Slot) -> Slot {
+    if slot.is_strong() && !value.is_strong() && identical(slot, &value) {
+        return value;
+    }
+    mem::replace(slot, value)
+}
+
+fn settle(outcome: Outcome) -> Result<Slot, RuntimeError> {
+    match outcome {
+        Ok(value) => Ok(value),
+        Err(Unwind::Return(..)) => Err(RuntimeError::new("return from a dead method context")),
+        Err(Unwind::Error(err)) => Err(err),
+    }
+}
+
+fn enclose(context: Strong<Context>, value: &Slot) {
+    if !value.is_strong() {
+        return;
+    }
+    if let Some(mut object) = value.object().and_then(|o| o.try_write().ok()) {
+        if let Some(procedure) = object.as_procedure_mut() {
+            let _ = procedure.capture(context);
+        }
+    }
+}
+
+enum Binding {
+    Local(Weak<Context>, usize),
+    Field(Weak<Object>, usize),
+    Global,
+}
+
+pub struct Interpreter {
+    globals: HashMap<String, Slot>,
+    true_object: Strong<Object>,
+    false_object: Strong<Object>,
+    next_home: usize,
+    homes: Vec<usize>,
+    engine: Engine,
+    pub auto_declare: bool,
+    methods: HashMap<(usize, usize), (Arc<MethodDefinition>, Arc<Code>)>,
+}
+
+impl Interpreter {
+    pub fn with_engine(engine: Engine) -> Self {
+        primitives::install();
+        let mut globals: HashMap<String, Slot> = BUILTIN_CLASSES
+            .iter()
+            .map(|&class| {
+                let object = Strong::new(Object::class_object(class));
+                (class.name.to_string(), object.into())
+            })
+            .collect();
+        let system = Object::record(&SYSTEM_DICTIONARY).expect("SystemDictionary is a record");
+        globals.insert("Smalltalk".to_string(), Strong::new(system).into());
+        Self {
+            globals,
+            true_object: Strong::new(Object::boolean(true)),
+            false_object: Strong::new(Object::boolean(false)),
+            next_home: 0,
+            homes: Vec::new(),
+            engine,
+            auto_declare: false,
+            methods: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn run(&mut self, body: &Body) -> Result<Slot, RuntimeError> {
+        let home = self.enter_home();
+        let names = body.temporaries.iter().map(|t| intern(t)).collect();
+        let mut context = Context::new(names, vec![], None, home);
+        context.receiver = Some(Slot::nil());
+        let context = Strong::new(context);
+
+        let outcome = match self.engine {
+            Engine::Tree => self.eval_body(&context, body),
+            Engine::Bytecode => self.execute(&context, &compiler::compile_body(body)),
+        };
+        settle(self.finish(context, outcome, Some(home)))
+    }
+
+    pub fn declare(&mut self, name: &str) {
+        if !self.globals.contains_key(name) {
+            self.globals.insert(name.to_string(), Slot::nil());
+        }
+    }
+
+    /// Runs `body`, which went through [`infer_body`], and returns the
+    /// `printString` of its result.
+    pub fn evaluate(&mut self, body: &Body) -> Result<String, RuntimeError> {
+        let result = self.run(body)?;
+        self.display(result)
+    }
+
+    pub(crate) fn display(&mut self, value: Slot) -> Result<String, RuntimeError> {
+        let printed = settle(self.send(value, "printString", vec![]))?;
+        let string = self.with_object(&printed, |o| o.as_string().cloned());
+        match string.ok().flatten() {
+            Some(string) => Ok(string),
+            None => Ok(self.print_string(&printed)),
+        }
+    }
+
+    pub(crate) fn boolean(&self, value: bool) -> Slot {
+        match value {
+            true => Slot::from(self.true_object.alias()),
+            false => Slot::from(self.false_object.alias()),
+        }
+    }
+
+    pub(crate) fn truth(&self, slot: &Slot) -> Result<bool, Unwind> {
+        match self.with_object(slot, Object::as_boolean)? {
+            Some(value) => Ok(value),
+            None => error("not a Boolean"),
+        }
+    }
+
+    pub(crate) fn class_of(&self, slot: &Slot) -> Result<&'static Class, Unwind> {
+        if slot.as_int().is_some() {
+            Ok(&SMALL_INTEGER)
+        } else if slot.is_nil() {
+            Ok(&UNDEFINED_OBJECT)
+        } else {
+            self.with_object(slot, Object::class)
+        }
+    }
+
+    pub(crate) fn class_object(&self, class: &'static Class) -> Slot {
+        if let Some(global) = self.globals.get(class.name) {
+            if let Ok(Some(c)) = self.with_object(global, Object::as_class) {
+                if std::ptr::eq(c, class) {
+                    return global.alias();
+                }
+            }
+        }
+        Strong::new(Object::class_object(class)).into()
+    }
+
+    pub(crate) fn with_object<R>(
+        &self,
+        slot: &Slot,
+        f: impl FnOnce(&Object) -> R,
+    ) -> Result<R, Unwind> {
+        let Some(object) = slot.object() else {
+            return error("not an object");
+        };
+        match object.try_read() {
+            Ok(object) => Ok(f(&object)),
+            Err(err) => error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn with_object_mut<R>(
+        &self,
+        slot: &Slot,
+        f: impl FnOnce(&mut Object) -> R,
+    ) -> Result<R, Unwind> {
+        let Some(object) = slot.object() else {
+            return error("not an object");
+        };
+        match object.try_write() {
+            Ok(mut object) => Ok(f(&mut object)),
+            Err(err) => error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn print_string(&self, slot: &Slot) -> String {
+        if let Some(val) = slot.as_int() {
+            return val.to_string();
+        }
+        if slot.is_nil() {
+            return "nil".to_string();
+        }
+        let printed = self.with_object(slot, |o| {
+            if let Some(value) = o.as_boolean() {
+                value.to_string()
+            } else if let Some(string) = o.as_string() {
+                format!("'{}'", string.replace('\'', "''"))
+            } else if let Some(symbol) = o.as_symbol() {
+                format!("#{symbol}")
+            } else if let Some(class) = o.as_class() {
+                class.name.to_string()
+            } else if let Some(elements) = o.as_array() {
+                let elements: Vec<_> = elements.iter().map(|e| self.print_string(e)).collect();
+                format!("#({})", elements.join(" "))
+            } else {
+                let name = o.class().name;
+                match name.starts_with(['A', 'E', 'I', 'O', 'U']) {
+                    true => format!("an {name}"),
+                    false => format!("a {name}"),
+                }
+            }
+        });
+        printed.unwrap_or_else(|_| "a dangling reference".to_string())
+    }
+
+    pub(crate) fn send(&mut self, receiver: Slot, selector: &str, arguments: Vec<Slot>) -> Outcome {
+        let class = self.class_of(&receiver)?;
+        self.dispatch(class, receiver, selector, arguments)
+    }
+
+    fn dispatch(
+        &mut self,
+        class: &'static Class,
+        receiver: Slot,
+        selector: &str,
+        arguments: Vec<Slot>,
+    ) -> Outcome {
+        match class.lookup(selector) {
+            Some((defining, method)) => self.invoke(defining, method, receiver, arguments),
+            None => match class.lookup("doesNotUnderstand:") {
+                Some((defining, method)) => {
+                    let message = Object::message(intern(selector), arguments);
+                    let message = Strong::new(message).into();
+                    self.invoke(defining, method, receiver, vec![message])
+                }
+                None => error(format!("{} does not understand #{selector}", class.name)),
+            },
+        }
+    }
+
+    fn invoke(
+        &mut self,
+        defining: &'static Class,
+        method: Method,
+        receiver: Slot,
+        arguments: Vec<Slot>,
+    ) -> Outcome {
+        match method {
+            Method::Primitive(primitive) => primitive(self, receiver, arguments),
+            Method::Compiled(method) => self.activate(defining, &method, receiver, arguments),
+        }
+    }
+
+    fn activate(
+        &mut self,
+        defining: &'static Class,
+        method: &Arc<MethodDefinition>,
+        receiver: Slot,
+        arguments: Vec<Slot>,
+    ) -> Outcome {
+        if method.parameters.len() != arguments.len() {
+            return error(format!(
+                "#{} expects {} arguments, got {}",
+                method.selector,
+                method.parameters.len(),
+                arguments.len()
+            ));
+        }
+
+        let home = self.enter_home();
+        let names = method
+            .parameters
+            .iter()
+            .chain(&method.body.temporaries)
+            .map(|name| intern(name))
+            .collect();
+        let mut context = Context::new(names, arguments, None, home);
+        let result = receiver.alias();
+        context.receiver = Some(receiver);
+        context.method_class = Some(defining);
+        let context = Strong::new(context);
+
+        let outcome = match self.engine {
+            Engine::Tree => self.eval_body(&context, &method.body),
+            Engine::Bytecode => {
+                let code = self.method_code(defining, method);
+                self.execute(&context, &code)
+            }
+        };
+        self.finish(context, outcome.map(|_| result), Some(home))
+    }
+
+    fn method_code(
+        &mut self,
+        defining: &'static Class,
+        method: &Arc<MethodDefinition>,
+    ) -> Arc<Code> {
+        let key = (
+            Arc::as_ptr(method) as usize,
+            defining as *const Class as usize,
+        );
+        let (_, code) = self.methods.entry(key).or_insert_with(|| {
+            let code = compiler::compile_method(method, defining.instance_variables);
+            (method.clone(), Arc::new(code))
+        });
+        code.clone()
+    }
+
+    pub(crate) fn call(&mut self, procedure: &Slot, arguments: Vec<Slot>) -> Outcome {
+        let Some((mut names, block, code, outer, home)) = self.with_object(procedure, |o| {
+            o.as_procedure().map(|p| {
+                let code = p.code.clone();
+                (p.parameters.clone(), p.block.clone(), code, p.outer, p.home)
+            })
+        })?
+        else {
+            return error("not a block");
+        };
+
+        if names.len() != arguments.len() {
+            return error(format!(
+                "block expects {} arguments, got {}",
+                names.len(),
+                arguments.len()
+            ));
+        }
+
+        names.extend(block.body.temporaries.iter().map(|t| intern(t)));
+        let context = Strong::new(Context::new(names, arguments, Some(outer), home));
+        let outcome = match code {
+            Some(code) => self.execute(&context, &code),
+            None => self.eval_body(&context, &block.body),
+        };
+        self.finish(context, outcome, None)
+    }
+
+    fn finish(
+        &mut self,
+        context: Strong<Context>,
+        outcome: Outcome,
+        home: Option<usize>,
+    ) -> Outcome {
+        if home.is_some() {
+            self.homes.pop();
+        }
+        let rescue = |value| match context.try_write() {
+            Ok(mut context) => context.rescue(value),
+            Err(_) => value,
+        };
+        let outcome = match outcome {
+            Ok(value) => Ok(rescue(value)),
+            Err(Unwind::Return(value, h)) if Some(h) == home => Ok(rescue(value)),
+            Err(Unwind::Return(value, h)) => Err(Unwind::Return(rescue(value), h)),
+            Err(err) => Err(err),
+        };
+        if let Ok(value) | Err(Unwind::Return(value, _)) = &outcome {
+            enclose(context, value);
+        }
+        outcome
+    }
+
+    fn enter_home(&mut self) -> usize {
+        self.next_home += 1;
+        self.homes.push(self.next_home);
+        self.next_home
+    }
+
+    fn eval_body(&mut self, context: &Strong<Context>, body: &Body) -> Outcome {
+        let mut result = Slot::nil();
+        for statement in &body.statements {
+            match statement {
+                Statement::Return(expr, _) => {
+                    let value = self.eval(context, expr)?;
+                    let home = self.read_context(&context.alias())?.home;
+                    if !self.homes.contains(&home) {
+                        return error("block cannot return, its home method has returned")
+                            .map_err(|e| e.at(expr.span));
+                    }
+                    return Err(Unwind::Return(value, home));
+                }
+                Statement::Expression(expr) => {
+                    result = self.eval(context, expr)?;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn eval(&mut self, context: &Strong<Context>, expr: &Expr) -> Outcome {
+        match &expr.kind {
+            ExprKind::Literal(literal) => self.literal(literal).map_err(|e| e.at(expr.span)),
+            ExprKind::Variable(name) => self.lookup(context, name).map_err(|e| e.at(expr.span)),
+            ExprKind::Move(name) => self.take(context, name).map_err(|e| e.at(expr.span)),
+            ExprKind::Assign(name, value) => {
+                let value = self.eval(context, value)?;
+                self.assign(context, name, value)
+                    .map_err(|e| e.at(expr.span))
+            }
+            ExprKind::Send(receiver, message)
+                if receiver.kind == ExprKind::Variable("super".into()) =>
+            {
+                let class = self
+                    .method_class(context)
+                    .map_err(|e| e.at(receiver.span))?;
+                let receiver = self.lookup(context, "self")?;
+                let arguments = self.eval_arguments(context, message)?;
+                let Some(superclass) = class.superclass else {
+                    return error(format!("{} has no superclass", class.name))
+                        .map_err(|e| e.at(message.span));
+                };
+                self.dispatch(superclass, receiver, &message.selector, arguments)
+                    .map_err(|e| e.at(message.span))
+            }
+            ExprKind::Send(receiver, message) => {
+                let receiver = self.eval(context, receiver)?;
+                self.eval_message(context, receiver, message)
+            }
+            ExprKind::Cascade(receiver, messages) => {
+                let receiver = self.eval(context, receiver)?;
+                let mut result = Slot::nil();
+                for message in messages {
+                    result = self.eval_message(context, receiver.alias(), message)?;
+                }
+                match receiver.is_strong() && identical(&receiver, &result) {
+                    true => Ok(receiver),
+                    false => Ok(result),
+                }
+            }
+            ExprKind::Block(block) => {
+                let home = self.read_context(&context.alias())?.home;
+                let procedure = Procedure::new(block.clone(), context.alias(), home);
+                Ok(Strong::new(Object::procedure(procedure)).into())
+            }
+            ExprKind::Brace(items) => {
+                let mut elements = Vec::with_capacity(items.len());
+                for item in items {
+                    elements.push(self.eval(context, item)?);
+                }
+                Ok(Strong::new(Object::array(elements)).into())
+            }
+            ExprKind::Class(definition) => {
+                self.define_class(definition).map_err(|e| e.at(expr.span))
+            }
+        }
+    }
+
+    fn eval_arguments(
+        &mut self,
+        context: &Strong<Context>,
+        message: &Message,
+    ) -> Result<Vec<Slot>, Unwind> {
+        let mut arguments = Vec::with_capacity(message.arguments.len());
+        for argument in &message.arguments {
+            arguments.push(self.eval(context, argument)?);
+        }
+        Ok(arguments)
+    }
+
+    fn eval_message(
+        &mut self,
+        context: &Strong<Context>,
+        receiver: Slot,
+        message: &Message,
+    ) -> Outcome {
+        let arguments = self.eval_arguments(context, message)?;
+        self.send(receiver, &message.selector, arguments)
+            .map_err(|e| e.at(message.span))
+    }
+
+    fn literal(&self, literal: &Literal) -> Outcome {
+        match literal {
+            Literal::Nil => Ok(Slot::nil()),
+            Literal::True => Ok(self.boolean(true)),
+            Literal::False => Ok(self.boolean(false)),
+            Literal::Integer(val) => Ok(Slot::int(*val)),
+            Literal::String(string) => Ok(Strong::new(Object::string(string.clone())).into()),
+            Literal::Symbol(name) => Ok(Strong::new(Object::symbol(intern(name))).into()),
+            Literal::Array(items) => {
+                let mut elements = Vec::with_capacity(items.len());
+                for item in items {
+                    elements.push(self.literal(item)?);
+                }
+                Ok(Strong::new(Object::array(elements)).into())
+            }
+        }
+    }
+
+    fn define_class(&mut self, definition: &ClassDefinition) -> Outcome {
+        let class = match &definition.superclass {
+            Some(superclass) => {
+                let superclass = self.global_class(superclass)?;
+                let instance_variables: Vec<_> = definition
+                    .instance_variables
+                    .iter()
+                    .map(|name| intern(name))
+                    .collect();
+                let name = intern(&definition.name);
+                let Some(class) = Class::subclass(superclass, name, &instance_variables) else {
+                    return error(format!("cannot subclass {}", superclass.name));
+                };
+                let object = Strong::new(Object::class_object(class));
+                self.globals.insert(definition.name.clone(), object.into());
+                class
+            }
+            None => self.global_class(&definition.name)?,
+        };
+
+        for method in &definition.methods {
+            class.define(intern(&method.selector), Method::Compiled(method.clone()));
+        }
+        Ok(self.class_object(class))
+    }
+
+    fn global_class(&self, name: &str) -> Result<&'static Class, Unwind> {
+        let class = match self.globals.get(name) {
+            Some(global) => self.with_object(global, Object::as_class)?,
+            None => None,
+        };
+        match class {
+            Some(class) => Ok(class),
+            None => error(format!("{name} is not a class")),
+        }
+    }
+
+    fn read_context(&self, context: &Weak<Context>) -> Result<Reading<Context>, Unwind> {
+        match context.try_read() {
+            Ok(context) => Ok(context),
+            Err(err) => context_error(err),
+        }
+    }
+
+    fn method_class(&self, context: &Strong<Context>) -> Result<&'static Class, Unwind> {
+        let mut current = context.alias();
+        loop {
+            let context = self.read_context(&current)?;
+            if let Some(class) = context.method_class {
+                return Ok(class);
+            }
+            match context.outer {
+                Some(outer) => current = outer,
+                None => return error("super used outside of a method"),
+            }
+        }
+    }
+
+    fn resolve(&self, context: &Strong<Context>, name: &str) -> Result<Binding, Unwind> {
+        let mut current = context.alias();
+        loop {
+            let context = self.read_context(&current)?;
+            if let Some(index) = context.index(name) {
+                return Ok(Binding::Local(current, index));
+            }
+            if let Some(receiver) = &context.receiver {
+                if let Some(object) = receiver.object() {
+                    let class = self.with_object(receiver, Object::class)?;
+                    if let Some(index) = class.instance_variable(name) {
+                        return Ok(Binding::Field(object, index));
+                    }
+                }
+            }
+            match context.outer {
+                Some(outer) => current = outer,
+                None => return Ok(Binding::Global),
+            }
+        }
+    }
+
+    fn assign_global(&mut self, name: &str, value: Slot) -> Result<Slot, Unwind> {
+        if self.auto_declare {
+            self.declare(name);
+        }
+        match self.globals.get_mut(name) {
+            Some(global) => Ok(store(global, value)),
+            None => error(format!("assignment to undeclared variable {name}")),
+        }
+    }
+
+    fn receiver(&self, context: &Strong<Context>) -> Outcome {
+        let mut current = context.alias();
+        loop {
+            let context = self.read_context(&current)?;
+            if let Some(receiver) = &context.receiver {
+                return Ok(receiver.alias());
+            }
+            match context.outer {
+                Some(outer) => current = outer,
+                None => return Ok(Slot::nil()),
+            }
+        }
+    }
+
+    fn lookup(&self, context: &Strong<Context>, name: &str) -> Outcome {
+        if let "self" | "super" = name {
+            return self.receiver(context);
+        }
+
+        match self.resolve(context, name)? {
+            Binding::Local(context, index) => Ok(self.read_context(&context)?.get(index).alias()),
+            Binding::Field(object, index) => {
+                let field = self.with_object(&Slot::from(object), |o| {
+                    o.as_record().map(|fields| fields[index].alias())
+                })?;
+                Ok(field.unwrap_or_else(Slot::nil))
+            }
+            Binding::Global => match self.globals.get(name) {
+                Some(global) => Ok(global.alias()),
+                None => error(format!("undefined variable {name}")),
+            },
+        }
+    }
+
+    fn take(&self, context: &Strong<Context>, name: &str) -> Outcome {
+        match self.resolve(context, name)? {
+            Binding::Local(context, index) => match context.try_write() {
+                Ok(mut context) => Ok(context.take(index)),
+                Err(err) => context_error(err),
+            },
+            _ => self.lookup(context, name),
+        }
+    }
+
+    fn assign(&mut self, context: &Strong<Context>, name: &str, value: Slot) -> Outcome {
+        let result = value.alias();
+        let old = match self.resolve(context, name)? {
+            Binding::Local(context, index) => match context.try_write() {
+                Ok(mut context) => context.set(index, value),
+                Err(err) => return context_error(err),
+            },
+            Binding::Field(object, index) => {
+                let old = self.with_object_mut(&Slot::from(object), |o| {
+                    o.as_record_mut()
+                        .map(|fields| store(&mut fields[index], value))
+                })?;
+                match old {
+                    Some(old) => old,
+                    None => return error("not a record"),
+                }
+            }
+            Binding::Global => self.assign_global(name, value)?,
+        };
+        drop(old);
+        Ok(result)
+    }
+}
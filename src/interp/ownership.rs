@@ -0,0 +1,359 @@
+use std::sync::Arc;
+use std::{fmt, mem};
+
+use super::compiler::inlinable;
+use crate::memory::pointers::OwnershipBit;
+use crate::syntax::{
+    Block, Body, ClassDefinition, Expr, ExprKind, Literal, Message, MethodDefinition, Span,
+    Statement,
+};
+
+/// Primitives that store their last argument into the receiver.
+const STORES: &[&str] = &["at:put:"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Own {
+    Copy,
+    Owned,
+    Alias,
+    Unknown,
+    Moved(Span),
+    MaybeMoved(Span),
+}
+
+impl Own {
+    fn join(self, other: Own) -> Own {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Own::Moved(span), Own::Moved(_)) => Own::Moved(span),
+            (Own::Moved(span) | Own::MaybeMoved(span), _)
+            | (_, Own::Moved(span) | Own::MaybeMoved(span)) => Own::MaybeMoved(span),
+            _ => Own::Unknown,
+        }
+    }
+
+    // Only `MaybeMoved` is left undecided, and it is reported instead. A local
+    // whose slot is only known at run time is moved: taking a slot that turns
+    // out not to own hands over an alias, which is all it had.
+    fn ownership(self) -> Option<OwnershipBit> {
+        match self {
+            Own::Copy => Some(OwnershipBit::Copy),
+            Own::Owned | Own::Unknown => Some(OwnershipBit::Strong),
+            Own::Alias | Own::Moved(_) => Some(OwnershipBit::Weak),
+            Own::MaybeMoved(_) => None,
+        }
+    }
+}
+
+type Scopes = Vec<Vec<(String, Own)>>;
+
+fn join(a: Scopes, b: Scopes) -> Scopes {
+    a.into_iter()
+        .zip(b)
+        .map(|(a, b)| {
+            a.into_iter()
+                .zip(b)
+                .map(|((name, a), (_, b))| (name, a.join(b)))
+                .collect()
+        })
+        .collect()
+}
+
+fn classify(kind: &ExprKind) -> Own {
+    match kind {
+        ExprKind::Literal(Literal::Nil | Literal::True | Literal::False | Literal::Integer(_)) => {
+            Own::Copy
+        }
+        ExprKind::Literal(_) | ExprKind::Block(_) | ExprKind::Brace(_) => Own::Owned,
+        ExprKind::Send(..) | ExprKind::Cascade(..) => Own::Unknown,
+        _ => Own::Alias,
+    }
+}
+
+/// Decides, for every store of a local into a longer-lived variable or into
+/// an object through a storing primitive, whether the local hands over its
+/// strong slot or only lends an alias. Owners are rewritten into
+/// `ExprKind::Move`. A store that moves on some paths and lends on others is
+/// reported, together with every other one in the body and its methods.
+pub fn infer_body(body: &Body) -> Result<Body, Vec<Diagnostic>> {
+    let mut inference = Inference::default();
+    inference.enter(&[], &body.temporaries);
+    let body = inference.body(body);
+    inference.finish(body)
+}
+
+fn infer_method(method: &MethodDefinition, diagnostics: &mut Vec<Diagnostic>) -> MethodDefinition {
+    let mut inference = Inference::default();
+    inference.enter(&method.parameters, &method.body.temporaries);
+    let body = inference.body(&method.body);
+    diagnostics.append(&mut inference.diagnostics);
+    MethodDefinition {
+        selector: method.selector.clone(),
+        parameters: method.parameters.clone(),
+        body,
+        span: method.span,
+    }
+}
+
+#[derive(Default)]
+struct Inference {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Inference {
+    fn finish(self, body: Body) -> Result<Body, Vec<Diagnostic>> {
+        match self.diagnostics.is_empty() {
+            true => Ok(body),
+            false => Err(self.diagnostics),
+        }
+    }
+
+    fn enter(&mut self, parameters: &[String], temporaries: &[String]) {
+        let parameters = parameters.iter().map(|p| (p.clone(), Own::Unknown));
+        let temporaries = temporaries.iter().map(|t| (t.clone(), Own::Copy));
+        self.scopes.push(parameters.chain(temporaries).collect());
+    }
+
+    fn find(&self, name: &str) -> Option<(usize, usize)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(level, names)| {
+                let index = names.iter().rposition(|(n, _)| n == name)?;
+                Some((level, index))
+            })
+    }
+
+    fn escapes(&self, target: &str, source: &str) -> bool {
+        match (self.find(target), self.find(source)) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some((target, _)), Some((source, _))) => target < source,
+        }
+    }
+
+    fn transfer(&mut self, name: &str, span: Span) -> (Expr, Own) {
+        let (level, index) = self.find(name).expect("escaping stores read locals");
+        let state = &mut self.scopes[level][index].1;
+        let own = *state;
+        let kind = match own.ownership() {
+            Some(OwnershipBit::Strong) => {
+                *state = Own::Moved(span);
+                ExprKind::Move(name.to_string())
+            }
+            Some(_) => ExprKind::Variable(name.to_string()),
+            None => {
+                self.diagnostics.push(Diagnostic {
+                    message: format!("ambiguous move of {name}"),
+                    span,
+                });
+                ExprKind::Variable(name.to_string())
+            }
+        };
+        let own = match kind {
+            ExprKind::Move(_) => own,
+            _ => Own::Alias,
+        };
+        (Expr { kind, span }, own)
+    }
+
+    fn body(&mut self, body: &Body) -> Body {
+        let statements = body
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Expression(expr) => Statement::Expression(self.expr(expr)),
+                Statement::Return(expr, span) => Statement::Return(self.expr(expr), *span),
+            })
+            .collect();
+        Body {
+            temporaries: body.temporaries.clone(),
+            statements,
+            span: body.span,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Expr {
+        let kind = match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::Move(_) => expr.kind.clone(),
+            ExprKind::Assign(name, value) => {
+                let (value, own) = match &value.kind {
+                    ExprKind::Variable(source) if self.escapes(name, source) => {
+                        self.transfer(source, value.span)
+                    }
+                    _ => (self.expr(value), classify(&value.kind)),
+                };
+                if let Some((level, index)) = self.find(name) {
+                    self.scopes[level][index].1 = own;
+                }
+                ExprKind::Assign(name.clone(), Box::new(value))
+            }
+            ExprKind::Send(receiver, message) => match self.inline(receiver, message) {
+                Some(kind) => kind,
+                None => {
+                    let receiver = self.expr(receiver);
+                    ExprKind::Send(Box::new(receiver), self.message(message, None))
+                }
+            },
+            ExprKind::Cascade(receiver, messages) => {
+                let receiver = self.expr(receiver);
+                let messages = messages
+                    .iter()
+                    .map(|message| self.message(message, None))
+                    .collect();
+                ExprKind::Cascade(Box::new(receiver), messages)
+            }
+            ExprKind::Block(block) => ExprKind::Block(self.closure(block)),
+            ExprKind::Brace(items) => {
+                ExprKind::Brace(items.iter().map(|item| self.expr(item)).collect())
+            }
+            ExprKind::Class(definition) => ExprKind::Class(Arc::new(ClassDefinition {
+                name: definition.name.clone(),
+                superclass: definition.superclass.clone(),
+                instance_variables: definition.instance_variables.clone(),
+                methods: definition
+                    .methods
+                    .iter()
+                    .map(|method| Arc::new(infer_method(method, &mut self.diagnostics)))
+                    .collect(),
+                span: definition.span,
+            })),
+        };
+        Expr {
+            kind,
+            span: expr.span,
+        }
+    }
+
+    fn argument(&mut self, argument: &Expr, stored: bool) -> Expr {
+        match &argument.kind {
+            ExprKind::Variable(source) if stored && self.find(source).is_some() => {
+                self.transfer(source, argument.span).0
+            }
+            _ => self.expr(argument),
+        }
+    }
+
+    fn message(&mut self, message: &Message, arms: Option<Vec<Arc<Block>>>) -> Message {
+        let stored = STORES.contains(&message.selector.as_str());
+        let last = message.arguments.len().saturating_sub(1);
+        let arguments = match arms {
+            Some(arms) => message
+                .arguments
+                .iter()
+                .zip(arms)
+                .map(|(argument, block)| Expr {
+                    kind: ExprKind::Block(block),
+                    span: argument.span,
+                })
+                .collect(),
+            None => message
+                .arguments
+                .iter()
+                .enumerate()
+                .map(|(i, argument)| self.argument(argument, stored && i == last))
+                .collect(),
+        };
+        Message {
+            selector: message.selector.clone(),
+            arguments,
+            span: message.span,
+        }
+    }
+
+    fn inline(&mut self, receiver: &Expr, message: &Message) -> Option<ExprKind> {
+        let arms = message
+            .arguments
+            .iter()
+            .map(inlinable)
+            .collect::<Option<Vec<_>>>()?;
+        if receiver.kind == ExprKind::Variable("super".into()) {
+            return None;
+        }
+        match message.selector.as_str() {
+            "ifTrue:" | "ifFalse:" | "ifTrue:ifFalse:" | "ifFalse:ifTrue:" => {
+                let receiver = self.expr(receiver);
+                let entry = self.scopes.clone();
+                let mut exits = Vec::new();
+                if arms.len() == 1 {
+                    exits.push(entry.clone());
+                }
+                let mut blocks = Vec::with_capacity(arms.len());
+                for block in arms {
+                    self.scopes = entry.clone();
+                    blocks.push(self.inlined(block));
+                    exits.push(mem::take(&mut self.scopes));
+                }
+                self.scopes = exits.into_iter().reduce(join).unwrap_or(entry);
+                let message = self.message(message, Some(blocks));
+                Some(ExprKind::Send(Box::new(receiver), message))
+            }
+            "whileTrue:" | "whileFalse:" => {
+                let condition = inlinable(receiver)?;
+                let (condition, body) = self.iterate(condition, arms[0]);
+                let receiver = Expr {
+                    kind: ExprKind::Block(condition),
+                    span: receiver.span,
+                };
+                let message = self.message(message, Some(vec![body]));
+                Some(ExprKind::Send(Box::new(receiver), message))
+            }
+            _ => None,
+        }
+    }
+
+    // Runs the loop until the state at its head stops changing, and keeps
+    // only the diagnostics of that last round. The loop is left through the
+    // condition, so that is the state after it.
+    fn iterate(&mut self, condition: &Block, body: &Block) -> (Arc<Block>, Arc<Block>) {
+        let entry = self.scopes.clone();
+        let reported = self.diagnostics.len();
+        loop {
+            let head = self.scopes.clone();
+            let condition = self.inlined(condition);
+            let exit = self.scopes.clone();
+            let body = self.inlined(body);
+            let next = join(entry.clone(), mem::take(&mut self.scopes));
+            if next == head {
+                self.scopes = exit;
+                return (condition, body);
+            }
+            self.diagnostics.truncate(reported);
+            self.scopes = next;
+        }
+    }
+
+    fn inlined(&mut self, block: &Block) -> Arc<Block> {
+        Arc::new(Block {
+            parameters: block.parameters.clone(),
+            body: self.body(&block.body),
+            span: block.span,
+        })
+    }
+
+    // A block may run any number of times, or never, after it is created.
+    // Its body is analyzed once from the current state; what it moves out of
+    // enclosing scopes is only maybe moved afterwards.
+    fn closure(&mut self, block: &Block) -> Arc<Block> {
+        let entry = self.scopes.clone();
+        self.enter(&block.parameters, &block.body.temporaries);
+        let rewritten = self.inlined(block);
+        self.scopes.pop();
+        self.scopes = join(entry, mem::take(&mut self.scopes));
+        rewritten
+    }
+}
@@ -0,0 +1,621 @@
+use std::collections::hash_map::Entry;
+use std::sync::Once;
+
+use super::*;
+use crate::memory;
+use crate::object::{class, interner, Format, Interner, Symbol};
+
+pub(crate) type Primitive = fn(&mut Interpreter, Slot, Vec<Slot>) -> Outcome;
+
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let tables: [(&Class, &[(Symbol, Primitive)]); 11] = [
+            (&class::OBJECT, OBJECT),
+            (&class::UNDEFINED_OBJECT, UNDEFINED_OBJECT),
+            (&class::SMALL_INTEGER, INTEGER),
+            (&class::BOOLEAN, BOOLEAN),
+            (&class::STRING, STRING),
+            (&class::SYMBOL, SYMBOL),
+            (&class::ARRAY, ARRAY),
+            (&class::BAG, BAG),
+            (&class::MESSAGE, MESSAGE),
+            (&class::BLOCK_CLOSURE, PROCEDURE),
+            (&class::SYSTEM_DICTIONARY, SYSTEM_DICTIONARY),
+        ];
+        for class in class::BUILTIN_CLASSES {
+            Interner::intern_static(class.name);
+        }
+        for (class, table) in tables {
+            for &(selector, primitive) in table {
+                class.define(
+                    Interner::intern_static(selector),
+                    Method::Primitive(primitive),
+                );
+            }
+        }
+        for &(selector, primitive) in CLASS {
+            class::CLASS.define(
+                Interner::intern_static(selector),
+                Method::Primitive(primitive),
+            );
+        }
+    });
+}
+
+fn symbol_argument(
+    interp: &Interpreter,
+    arguments: &[Slot],
+    index: usize,
+) -> Result<Symbol, Unwind> {
+    match interp.with_object(&arguments[index], Object::as_symbol) {
+        Ok(Some(symbol)) => Ok(symbol),
+        _ => error("argument is not a Symbol"),
+    }
+}
+
+fn class_argument(
+    interp: &Interpreter,
+    arguments: &[Slot],
+    index: usize,
+) -> Result<&'static Class, Unwind> {
+    match interp.with_object(&arguments[index], Object::as_class) {
+        Ok(Some(class)) => Ok(class),
+        _ => error("argument is not a Class"),
+    }
+}
+
+fn receiver_class(interp: &Interpreter, receiver: &Slot) -> Result<&'static Class, Unwind> {
+    match interp.with_object(receiver, Object::as_class)? {
+        Some(class) => Ok(class),
+        None => error("receiver is not a Class"),
+    }
+}
+
+fn index_argument(arguments: &[Slot], size: usize) -> Result<usize, Unwind> {
+    let index = int_argument(arguments, 0)?;
+    match usize::try_from(index) {
+        Ok(i @ 1..) if i <= size => Ok(i - 1),
+        _ => error(format!("index {index} out of bounds")),
+    }
+}
+
+fn int_argument(arguments: &[Slot], index: usize) -> Result<i128, Unwind> {
+    match arguments.get(index).and_then(Slot::as_int) {
+        Some(val) => Ok(val),
+        None => error("argument is not an Integer"),
+    }
+}
+
+fn int_receiver(receiver: &Slot) -> i128 {
+    receiver.as_int().unwrap_or_default()
+}
+
+fn checked(result: Option<i128>) -> Outcome {
+    match result {
+        Some(val) => Ok(Slot::int(val)),
+        None => error("integer overflow"),
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> Option<i128> {
+    let q = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        Some(q - 1)
+    } else {
+        Some(q)
+    }
+}
+
+fn floor_mod(a: i128, b: i128) -> Option<i128> {
+    let r = a.checked_rem(b)?;
+    if r != 0 && (r < 0) != (b < 0) {
+        r.checked_add(b)
+    } else {
+        Some(r)
+    }
+}
+
+fn nonzero(divisor: i128) -> Result<i128, Unwind> {
+    match divisor {
+        0 => error("division by zero"),
+        _ => Ok(divisor),
+    }
+}
+
+macro_rules! arithmetic {
+    ($method:ident) => {
+        |_, receiver, arguments| {
+            checked(int_receiver(&receiver).$method(int_argument(&arguments, 0)?))
+        }
+    };
+}
+
+macro_rules! comparison {
+    ($op:tt) => {
+        |interp, receiver, arguments| {
+            let val = int_argument(&arguments, 0)?;
+            Ok(interp.boolean(int_receiver(&receiver) $op val))
+        }
+    };
+}
+
+static INTEGER: &[(Symbol, Primitive)] = &[
+    ("+", arithmetic!(checked_add)),
+    ("-", arithmetic!(checked_sub)),
+    ("*", arithmetic!(checked_mul)),
+    ("/", |_, receiver, arguments| {
+        let (a, b) = (
+            int_receiver(&receiver),
+            nonzero(int_argument(&arguments, 0)?)?,
+        );
+        match a.checked_rem(b) {
+            Some(0) => checked(a.checked_div(b)),
+            Some(_) => error("inexact integer division"),
+            None => error("integer overflow"),
+        }
+    }),
+    ("//", |_, receiver, arguments| {
+        let b = nonzero(int_argument(&arguments, 0)?)?;
+        checked(floor_div(int_receiver(&receiver), b))
+    }),
+    ("\\\\", |_, receiver, arguments| {
+        let (a, b) = (
+            int_receiver(&receiver),
+            nonzero(int_argument(&arguments, 0)?)?,
+        );
+        checked(floor_mod(a, b))
+    }),
+    ("quo:", |_, receiver, arguments| {
+        let b = nonzero(int_argument(&arguments, 0)?)?;
+        checked(int_receiver(&receiver).checked_div(b))
+    }),
+    ("rem:", |_, receiver, arguments| {
+        let b = nonzero(int_argument(&arguments, 0)?)?;
+        checked(int_receiver(&receiver).checked_rem(b))
+    }),
+    ("<", comparison!(<)),
+    (">", comparison!(>)),
+    ("<=", comparison!(<=)),
+    (">=", comparison!(>=)),
+    ("=", |interp, receiver, arguments| {
+        let equal = arguments[0].as_int() == receiver.as_int();
+        Ok(interp.boolean(equal))
+    }),
+    ("~=", |interp, receiver, arguments| {
+        let equal = arguments[0].as_int() == receiver.as_int();
+        Ok(interp.boolean(!equal))
+    }),
+    ("negated", |_, receiver, _| {
+        checked(int_receiver(&receiver).checked_neg())
+    }),
+    ("abs", |_, receiver, _| {
+        checked(int_receiver(&receiver).checked_abs())
+    }),
+    ("max:", |_, receiver, arguments| {
+        Ok(Slot::int(
+            int_receiver(&receiver).max(int_argument(&arguments, 0)?),
+        ))
+    }),
+    ("min:", |_, receiver, arguments| {
+        Ok(Slot::int(
+            int_receiver(&receiver).min(int_argument(&arguments, 0)?),
+        ))
+    }),
+    ("timesRepeat:", |interp, receiver, arguments| {
+        for _ in 0..int_receiver(&receiver) {
+            interp.call(&arguments[0], vec![])?;
+        }
+        Ok(receiver)
+    }),
+    ("to:do:", |interp, receiver, arguments| {
+        let stop = int_argument(&arguments, 0)?;
+        for i in int_receiver(&receiver)..=stop {
+            interp.call(&arguments[1], vec![Slot::int(i)])?;
+        }
+        Ok(receiver)
+    }),
+];
+
+static UNDEFINED_OBJECT: &[(Symbol, Primitive)] = &[
+    ("isNil", |interp, _, _| Ok(interp.boolean(true))),
+    ("notNil", |interp, _, _| Ok(interp.boolean(false))),
+    ("ifNil:", |interp, _, arguments| {
+        interp.call(&arguments[0], vec![])
+    }),
+    ("ifNotNil:", |_, _, _| Ok(Slot::nil())),
+    ("ifNil:ifNotNil:", |interp, _, arguments| {
+        interp.call(&arguments[0], vec![])
+    }),
+];
+
+static BOOLEAN: &[(Symbol, Primitive)] = &[
+    ("ifTrue:", |interp, receiver, arguments| {
+        match interp.truth(&receiver)? {
+            true => interp.call(&arguments[0], vec![]),
+            false => Ok(Slot::nil()),
+        }
+    }),
+    ("ifFalse:", |interp, receiver, arguments| {
+        match interp.truth(&receiver)? {
+            true => Ok(Slot::nil()),
+            false => interp.call(&arguments[0], vec![]),
+        }
+    }),
+    (
+        "ifTrue:ifFalse:",
+        |interp, receiver, arguments| match interp.truth(&receiver)? {
+            true => interp.call(&arguments[0], vec![]),
+            false => interp.call(&arguments[1], vec![]),
+        },
+    ),
+    (
+        "ifFalse:ifTrue:",
+        |interp, receiver, arguments| match interp.truth(&receiver)? {
+            true => interp.call(&arguments[1], vec![]),
+            false => interp.call(&arguments[0], vec![]),
+        },
+    ),
+    ("and:", |interp, receiver, arguments| {
+        match interp.truth(&receiver)? {
+            true => interp.call(&arguments[0], vec![]),
+            false => Ok(receiver),
+        }
+    }),
+    ("or:", |interp, receiver, arguments| {
+        match interp.truth(&receiver)? {
+            true => Ok(receiver),
+            false => interp.call(&arguments[0], vec![]),
+        }
+    }),
+    ("&", |interp, receiver, arguments| {
+        let value = interp.truth(&receiver)? && interp.truth(&arguments[0])?;
+        Ok(interp.boolean(value))
+    }),
+    ("|", |interp, receiver, arguments| {
+        let value = interp.truth(&receiver)? || interp.truth(&arguments[0])?;
+        Ok(interp.boolean(value))
+    }),
+    ("not", |interp, receiver, _| {
+        let value = interp.truth(&receiver)?;
+        Ok(interp.boolean(!value))
+    }),
+];
+
+static PROCEDURE: &[(Symbol, Primitive)] = &[
+    ("value", |interp, receiver, arguments| {
+        interp.call(&receiver, arguments)
+    }),
+    ("value:", |interp, receiver, arguments| {
+        interp.call(&receiver, arguments)
+    }),
+    ("value:value:", |interp, receiver, arguments| {
+        interp.call(&receiver, arguments)
+    }),
+    ("value:value:value:", |interp, receiver, arguments| {
+        interp.call(&receiver, arguments)
+    }),
+    ("value:value:value:value:", |interp, receiver, arguments| {
+        interp.call(&receiver, arguments)
+    }),
+    ("valueWithArguments:", |interp, receiver, arguments| {
+        let elements = interp.with_object(&arguments[0], |o| {
+            o.as_array()
+                .map(|elements| elements.iter().map(Slot::alias).collect())
+        });
+        match elements.ok().flatten() {
+            Some(elements) => interp.call(&receiver, elements),
+            None => error("argument is not an Array"),
+        }
+    }),
+    ("whileTrue:", |interp, receiver, arguments| {
+        while {
+            let condition = interp.call(&receiver, vec![])?;
+            interp.truth(&condition)?
+        } {
+            interp.call(&arguments[0], vec![])?;
+        }
+        Ok(Slot::nil())
+    }),
+    ("whileFalse:", |interp, receiver, arguments| {
+        while {
+            let condition = interp.call(&receiver, vec![])?;
+            !interp.truth(&condition)?
+        } {
+            interp.call(&arguments[0], vec![])?;
+        }
+        Ok(Slot::nil())
+    }),
+    ("whileTrue", |interp, receiver, _| {
+        while {
+            let condition = interp.call(&receiver, vec![])?;
+            interp.truth(&condition)?
+        } {}
+        Ok(Slot::nil())
+    }),
+    ("numArgs", |interp, receiver, _| {
+        let arity =
+            interp.with_object(&receiver, |o| o.as_procedure().map_or(0, Procedure::arity))?;
+        Ok(Slot::int(arity as i128))
+    }),
+];
+
+static OBJECT: &[(Symbol, Primitive)] = &[
+    ("==", |interp, receiver, arguments| {
+        Ok(interp.boolean(identical(&receiver, &arguments[0])))
+    }),
+    ("~~", |interp, receiver, arguments| {
+        Ok(interp.boolean(!identical(&receiver, &arguments[0])))
+    }),
+    ("=", |interp, receiver, arguments| {
+        Ok(interp.boolean(identical(&receiver, &arguments[0])))
+    }),
+    ("~=", |interp, receiver, arguments| {
+        Ok(interp.boolean(!identical(&receiver, &arguments[0])))
+    }),
+    ("isNil", |interp, _, _| Ok(interp.boolean(false))),
+    ("notNil", |interp, _, _| Ok(interp.boolean(true))),
+    ("ifNil:", |_, receiver, _| Ok(receiver)),
+    ("ifNotNil:", |interp, receiver, arguments| {
+        interp.call(&arguments[0], vec![receiver])
+    }),
+    ("ifNil:ifNotNil:", |interp, receiver, arguments| {
+        interp.call(&arguments[1], vec![receiver])
+    }),
+    ("yourself", |_, receiver, _| Ok(receiver)),
+    ("copy", |interp, receiver, _| {
+        let format = interp.with_object(&receiver, |o| o.class().format);
+        match format {
+            Err(_) if receiver.object().is_none() => Ok(receiver),
+            Ok(Format::Boolean | Format::Symbol | Format::Class) => Ok(receiver),
+            _ => match receiver.try_clone_strong() {
+                Some(copy) => Ok(copy),
+                None => error(format!("cannot copy {}", interp.print_string(&receiver))),
+            },
+        }
+    }),
+    ("printString", |interp, receiver, _| {
+        let printed = interp.print_string(&receiver);
+        Ok(Strong::new(Object::string(printed)).into())
+    }),
+    ("class", |interp, receiver, _| {
+        let class = interp.class_of(&receiver)?;
+        Ok(interp.class_object(class))
+    }),
+    ("isKindOf:", |interp, receiver, arguments| {
+        let class = class_argument(interp, &arguments, 0)?;
+        let kind = interp.class_of(&receiver)?.inherits_from(class);
+        Ok(interp.boolean(kind))
+    }),
+    ("respondsTo:", |interp, receiver, arguments| {
+        let selector = symbol_argument(interp, &arguments, 0)?;
+        let responds = interp.class_of(&receiver)?.lookup(selector).is_some();
+        Ok(interp.boolean(responds))
+    }),
+    ("doesNotUnderstand:", |interp, receiver, arguments| {
+        let class = interp.class_of(&receiver)?;
+        let selector = interp.with_object(&arguments[0], |o| o.as_message().map(|m| m.0))?;
+        error(format!(
+            "{} does not understand #{}",
+            class.name,
+            selector.unwrap_or_default()
+        ))
+    }),
+];
+
+static STRING: &[(Symbol, Primitive)] = &[
+    ("=", |interp, receiver, arguments| {
+        let a = interp.with_object(&receiver, |o| o.as_string().cloned())?;
+        let b = interp
+            .with_object(&arguments[0], |o| o.as_string().cloned())
+            .ok()
+            .flatten();
+        Ok(interp.boolean(a.is_some() && a == b))
+    }),
+    ("size", |interp, receiver, _| {
+        let size = interp.with_object(&receiver, |o| o.as_string().map(|s| s.chars().count()))?;
+        Ok(Slot::int(size.unwrap_or(0) as i128))
+    }),
+    (",", |interp, receiver, arguments| {
+        let a = interp.with_object(&receiver, |o| o.as_string().cloned())?;
+        let b = interp.with_object(&arguments[0], |o| o.as_string().cloned())?;
+        match (a, b) {
+            (Some(a), Some(b)) => Ok(Strong::new(Object::string(a + &b)).into()),
+            _ => error("argument is not a String"),
+        }
+    }),
+    ("asSymbol", |interp, receiver, _| {
+        let string = interp.with_object(&receiver, |o| o.as_string().map(|s| intern(s)))?;
+        match string {
+            Some(symbol) => Ok(Strong::new(Object::symbol(symbol)).into()),
+            None => error("not a String"),
+        }
+    }),
+];
+
+static SYMBOL: &[(Symbol, Primitive)] = &[
+    ("=", |interp, receiver, arguments| {
+        let a = interp.with_object(&receiver, Object::as_symbol)?;
+        let b = interp
+            .with_object(&arguments[0], Object::as_symbol)
+            .ok()
+            .flatten();
+        let same = matches!((a, b), (Some(a), Some(b)) if interner::identical(a, b));
+        Ok(interp.boolean(same))
+    }),
+    ("size", |interp, receiver, _| {
+        let symbol = interp.with_object(&receiver, Object::as_symbol)?;
+        Ok(Slot::int(symbol.map_or(0, |s| s.chars().count()) as i128))
+    }),
+    ("asString", |interp, receiver, _| {
+        let symbol = interp.with_object(&receiver, Object::as_symbol)?;
+        Ok(Strong::new(Object::string(symbol.unwrap_or("").to_string())).into())
+    }),
+];
+
+static ARRAY: &[(Symbol, Primitive)] = &[
+    ("size", |interp, receiver, _| {
+        let size = interp.with_object(&receiver, |o| o.as_array().map_or(0, Vec::len))?;
+        Ok(Slot::int(size as i128))
+    }),
+    ("at:", |interp, receiver, arguments| {
+        let element = interp.with_object(&receiver, |o| {
+            let elements = o.as_array()?;
+            Some(index_argument(&arguments, elements.len()).map(|i| elements[i].alias()))
+        })?;
+        element.unwrap_or_else(|| error("not an Array"))
+    }),
+    ("at:put:", |interp, receiver, mut arguments| {
+        let value = arguments.pop().unwrap_or_else(Slot::nil);
+        let result = value.alias();
+        let old = interp.with_object_mut(&receiver, |o| {
+            let elements = o.as_array_mut()?;
+            Some(index_argument(&arguments, elements.len()).map(|i| store(&mut elements[i], value)))
+        })?;
+        old.unwrap_or_else(|| error("not an Array"))?;
+        Ok(result)
+    }),
+    ("do:", |interp, receiver, arguments| {
+        let mut index = 0;
+        loop {
+            let element = interp.with_object(&receiver, |o| {
+                o.as_array()
+                    .and_then(|elements| elements.get(index).map(Slot::alias))
+            })?;
+            let Some(element) = element else {
+                return Ok(receiver);
+            };
+            interp.call(&arguments[0], vec![element])?;
+            index += 1;
+        }
+    }),
+];
+
+static BAG: &[(Symbol, Primitive)] = &[
+    ("size", |interp, receiver, _| {
+        let size = interp.with_object(&receiver, |o| o.as_bag().map_or(0, HashMap::len))?;
+        Ok(Slot::int(size as i128))
+    }),
+    ("at:", |interp, receiver, arguments| {
+        let key = symbol_argument(interp, &arguments, 0)?;
+        let value = interp.with_object(&receiver, |o| {
+            o.as_bag()
+                .map(|bag| bag.get(key).map_or_else(Slot::nil, Slot::alias))
+        })?;
+        value.map_or_else(|| error("not a Bag"), Ok)
+    }),
+    ("at:put:", |interp, receiver, mut arguments| {
+        let key = symbol_argument(interp, &arguments, 0)?;
+        let value = arguments.pop().unwrap_or_else(Slot::nil);
+        let result = value.alias();
+        let old = interp.with_object_mut(&receiver, |o| {
+            o.as_bag_mut().map(|bag| match bag.entry(key) {
+                Entry::Occupied(entry) => store(entry.into_mut(), value),
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                    Slot::nil()
+                }
+            })
+        })?;
+        match old {
+            Some(old) => {
+                drop(old);
+                Ok(result)
+            }
+            None => error("not a Bag"),
+        }
+    }),
+    ("includesKey:", |interp, receiver, arguments| {
+        let key = symbol_argument(interp, &arguments, 0)?;
+        let found =
+            interp.with_object(&receiver, |o| o.as_bag().map(|bag| bag.contains_key(key)))?;
+        Ok(interp.boolean(found == Some(true)))
+    }),
+];
+
+static SYSTEM_DICTIONARY: &[(Symbol, Primitive)] = &[("memoryStats", |_, _, _| {
+    let stats = memory::stats();
+    let entries = [
+        ("localAllocated", stats.local.allocated),
+        ("localFree", stats.local.free),
+        ("localRetired", stats.local.retired),
+        ("localGlobalized", stats.local.globalized),
+        ("localSlabBytes", stats.local.slab_bytes),
+        ("globalAllocated", stats.global.allocated),
+        ("globalFree", stats.global.free),
+        ("globalRetired", stats.global.retired),
+        ("globalGlobalized", stats.global.globalized),
+        ("globalSlabBytes", stats.global.slab_bytes),
+        ("parked", stats.parked),
+        ("strongs", stats.strongs),
+        ("readings", stats.readings),
+        ("writings", stats.writings),
+        ("symbols", Interner::count()),
+        ("symbolBytes", Interner::bytes()),
+    ];
+    let mut bag = Object::bag();
+    if let Some(map) = bag.as_bag_mut() {
+        for (key, value) in entries {
+            map.insert(intern(key), Slot::int(value as i128));
+        }
+    }
+    Ok(Strong::new(bag).into())
+})];
+
+static MESSAGE: &[(Symbol, Primitive)] = &[
+    ("selector", |interp, receiver, _| {
+        let selector = interp.with_object(&receiver, |o| o.as_message().map(|m| m.0))?;
+        match selector {
+            Some(selector) => Ok(Strong::new(Object::symbol(selector)).into()),
+            None => error("not a Message"),
+        }
+    }),
+    ("arguments", |interp, receiver, _| {
+        let arguments = interp.with_object(&receiver, |o| {
+            o.as_message()
+                .map(|m| m.1.iter().map(Slot::alias).collect())
+        })?;
+        match arguments {
+            Some(arguments) => Ok(Strong::new(Object::array(arguments)).into()),
+            None => error("not a Message"),
+        }
+    }),
+];
+
+static CLASS: &[(Symbol, Primitive)] = &[
+    ("new", |interp, receiver, _| {
+        let class = receiver_class(interp, &receiver)?;
+        match Object::instantiate(class) {
+            Some(object) => Ok(Strong::new(object).into()),
+            None => error(format!("cannot instantiate {} with #new", class.name)),
+        }
+    }),
+    ("new:", |interp, receiver, arguments| {
+        let class = receiver_class(interp, &receiver)?;
+        let size = int_argument(&arguments, 0)?;
+        if !std::ptr::eq(class, &class::ARRAY) {
+            return error(format!("cannot instantiate {} with #new:", class.name));
+        }
+        match usize::try_from(size) {
+            Ok(size) => {
+                Ok(Strong::new(Object::array((0..size).map(|_| Slot::nil()).collect())).into())
+            }
+            Err(_) => error("negative size"),
+        }
+    }),
+    ("name", |interp, receiver, _| {
+        let class = receiver_class(interp, &receiver)?;
+        Ok(Strong::new(Object::symbol(class.name)).into())
+    }),
+    ("superclass", |interp, receiver, _| {
+        let class = receiver_class(interp, &receiver)?;
+        Ok(class
+            .superclass
+            .map_or_else(Slot::nil, |c| interp.class_object(c)))
+    }),
+    ("inheritsFrom:", |interp, receiver, arguments| {
+        let class = receiver_class(interp, &receiver)?;
+        let other = class_argument(interp, &arguments, 0)?;
+        Ok(interp.boolean(!std::ptr::eq(class, other) && class.inherits_from(other)))
+    }),
+];
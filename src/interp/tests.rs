@@ -0,0 +1,576 @@
+#[cfg(test)]
+use super::*;
+
+#[cfg(test)]
+use crate::syntax;
+
+#[cfg(test)]
+fn evaluate(interp: &mut Interpreter, source: &str) -> Result<Slot, RuntimeError> {
+    interp.run(&infer_body(&syntax::parse(source).unwrap()).unwrap())
+}
+
+#[cfg(test)]
+fn diagnostics(source: &str) -> Vec<(String, &str)> {
+    let diagnostics = infer_body(&syntax::parse(source).unwrap()).err();
+    diagnostics
+        .unwrap_or_default()
+        .into_iter()
+        .map(|d| (d.message, &source[d.span.start..d.span.end]))
+        .collect()
+}
+
+#[cfg(test)]
+fn cross_check(source: &str) -> (Interpreter, Result<Slot, RuntimeError>) {
+    let mut tree = Interpreter::with_engine(Engine::Tree);
+    let mut bytecode = Interpreter::with_engine(Engine::Bytecode);
+    let expected = evaluate(&mut tree, source);
+    let actual = evaluate(&mut bytecode, source);
+    match (&expected, &actual) {
+        (Ok(a), Ok(b)) => assert_eq!(tree.print_string(a), bytecode.print_string(b)),
+        (Err(a), Err(b)) => assert_eq!(a, b),
+        _ => panic!("engines disagree on {source:?}"),
+    }
+    (bytecode, actual)
+}
+
+#[cfg(test)]
+fn int(source: &str) -> i128 {
+    cross_check(source).1.ok().unwrap().as_int().unwrap()
+}
+
+#[cfg(test)]
+fn truth(source: &str) -> bool {
+    let (interp, result) = cross_check(source);
+    interp.truth(&result.ok().unwrap()).ok().unwrap()
+}
+
+#[cfg(test)]
+fn failure(source: &str) -> RuntimeError {
+    cross_check(source).1.err().unwrap()
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(int("3 + 4"), 7);
+    assert_eq!(int("3 + 4 * 2"), 14);
+    assert_eq!(int("3 + (4 * 2)"), 11);
+    assert_eq!(int("7 // 2"), 3);
+    assert_eq!(int("-7 // 2"), -4);
+    assert_eq!(int("-7 \\\\ 2"), 1);
+    assert_eq!(int("-7 rem: 2"), -1);
+    assert_eq!(int("-7 quo: 2"), -3);
+    assert_eq!(int("12 / 4"), 3);
+    assert_eq!(int("5 negated abs max: 2"), 5);
+    assert_eq!(int("| a | a := 2. a := a * a. a + 1"), 5);
+    assert_eq!(int("^ 1. 2"), 1);
+
+    assert_eq!(failure("7 / 2").message, "inexact integer division");
+    assert_eq!(failure("1 // 0").message, "division by zero");
+    assert_eq!(
+        failure("170141183460469231731687303715884105727 + 1").message,
+        "integer overflow"
+    );
+    assert_eq!(int("-170141183460469231731687303715884105728 \\\\ 3"), 1);
+    assert_eq!(int("-170141183460469231731687303715884105728 \\\\ -3"), -2);
+    assert_eq!(
+        failure("-170141183460469231731687303715884105728 \\\\ -1").message,
+        "integer overflow"
+    );
+    assert_eq!(failure("1 + nil").message, "argument is not an Integer");
+}
+
+#[test]
+fn conditionals() {
+    assert!(truth("3 > 2"));
+    assert!(!truth("3 < 2"));
+    assert!(truth("(3 = 3) & (2 ~= 3)"));
+    assert!(truth("nil isNil"));
+    assert!(truth("true not not"));
+    assert!(truth("(1 < 2) or: [1 // 0]"));
+    assert!(!truth("(1 > 2) and: [1 // 0]"));
+
+    assert_eq!(int("3 > 2 ifTrue: [1] ifFalse: [2]"), 1);
+    assert_eq!(int("3 < 2 ifTrue: [1] ifFalse: [2]"), 2);
+    assert_eq!(int("false ifFalse: [5]"), 5);
+    assert_eq!(int("nil ifNil: [6]"), 6);
+
+    let mut interp = Interpreter::with_engine(Engine::Tree);
+    assert!(evaluate(&mut interp, "3 < 2 ifTrue: [1]").unwrap().is_nil());
+
+    assert_eq!(
+        failure("3 ifTrue: [1]").message,
+        "SmallInteger does not understand #ifTrue:"
+    );
+}
+
+#[test]
+fn block_invocation() {
+    assert_eq!(int("[3] value"), 3);
+    assert_eq!(int("[:x | x * 2] value: 21"), 42);
+    assert_eq!(int("[:x :y | | t | t := x - y. t] value: 10 value: 4"), 6);
+    assert_eq!(int("[:a :b | a] numArgs + [] numArgs"), 2);
+    assert_eq!(
+        int("| f | f := [:n | n + 1]. (f value: 1) + (f value: 2)"),
+        5
+    );
+
+    assert_eq!(
+        failure("[:x | x] value").message,
+        "block expects 1 arguments, got 0"
+    );
+}
+
+#[test]
+fn closures_share_context() {
+    assert_eq!(
+        int("| n inc | n := 0. inc := [n := n + 1]. inc value. inc value. n"),
+        2
+    );
+    assert_eq!(
+        int("| sum | sum := 0. 1 to: 10 do: [:i | sum := sum + i]. sum"),
+        55
+    );
+    assert_eq!(int("| i | i := 0. [i < 5] whileTrue: [i := i + 1]. i"), 5);
+    assert_eq!(int("| i | i := 0. 3 timesRepeat: [i := i + 2]. i"), 6);
+}
+
+#[test]
+fn non_local_return() {
+    assert_eq!(int("1 to: 10 do: [:i | i = 4 ifTrue: [^i]]. 0"), 4);
+    assert_eq!(int("| b | b := [:x | ^x]. (b value: 3) + 100"), 3);
+}
+
+#[test]
+#[cfg(not(feature = "debug-refs"))]
+fn dangling_alias() {
+    let err = failure("| a b | a := [1]. b := a. a := nil. b value");
+    assert_eq!(err.message, "dangling reference");
+
+    let source = "| a b | a := [1]. b := a. a := nil. b value";
+    let span = err.span.unwrap();
+    assert_eq!(&source[span.start..span.end], "value");
+}
+
+#[test]
+#[cfg(feature = "debug-refs")]
+fn dangling_alias() {
+    let source = "| a b | a := [1]. b := a. a := nil. b value";
+    for engine in [Engine::Tree, Engine::Bytecode] {
+        let mut interp = Interpreter::with_engine(engine);
+        let err = evaluate(&mut interp, source).err().unwrap();
+        assert!(err.message.starts_with("dangling reference, generation"));
+        assert!(err.message.contains("allocated at:"));
+        assert!(err.message.contains("freed at:"));
+
+        let span = err.span.unwrap();
+        assert_eq!(&source[span.start..span.end], "value");
+    }
+}
+
+#[test]
+fn classes_and_instance_variables() {
+    let source = "
+        | p q |
+        Object subclass: Point [
+            | x y |
+            setX: ax y: ay [ x := ax. y := ay ]
+            x [ ^x ]
+            y [ ^y ]
+            + other [ ^Point new setX: x + other x y: y + other y ]
+        ].
+        p := Point new setX: 3 y: 4.
+        q := p + (Point new setX: 10 y: 20).
+        q x * 100 + q y";
+    assert_eq!(int(source), 1324);
+
+    assert_eq!(
+        int("Object subclass: Box [ |v| ]. Box new isNil ifTrue: [1] ifFalse: [2]"),
+        2
+    );
+    assert!(truth("Object subclass: Box [ ]. Box new class == Box"));
+    assert!(truth("Object subclass: Box [ ]. Box superclass == Object"));
+    assert!(truth("3 class == SmallInteger"));
+}
+
+#[test]
+fn inheritance_and_super() {
+    let source = "
+        | b |
+        Object subclass: Animal [
+            | legs |
+            legs [ ^legs ]
+            init [ legs := 4 ]
+            describe [ ^self legs * 10 + self sound ]
+            sound [ ^0 ]
+        ].
+        Animal subclass: Bird [
+            init [ super init. legs := legs - 2 ]
+            sound [ ^super sound + 7 ]
+        ].
+        b := Bird new.
+        b init.
+        b describe";
+    assert_eq!(int(source), 27);
+
+    assert!(truth(
+        "Object subclass: A [ ]. A subclass: B [ ]. (B new isKindOf: A) & (B inheritsFrom: Object)"
+    ));
+    assert!(truth("(3 respondsTo: #+) & (3 respondsTo: #foo) not"));
+}
+
+#[test]
+fn extend_builtin_class() {
+    assert_eq!(
+        int("SmallInteger extend [ squaredForTest [ ^self * self ] ]. 7 squaredForTest"),
+        49
+    );
+}
+
+#[test]
+fn does_not_understand() {
+    assert_eq!(
+        failure("Object new frobnicate: 3").message,
+        "Object does not understand #frobnicate:"
+    );
+    assert_eq!(
+        failure("nil foo").message,
+        "UndefinedObject does not understand #foo"
+    );
+
+    let source = "
+        Object subclass: Recorder [
+            doesNotUnderstand: aMessage [ ^aMessage arguments at: 2 ]
+        ].
+        Recorder new foo: 1 bar: 42";
+    assert_eq!(int(source), 42);
+
+    let source = "
+        Object subclass: Echo [
+            doesNotUnderstand: aMessage [ ^aMessage selector ]
+        ].
+        (Echo new foo: 1 bar: 2) = #foo:bar:";
+    assert!(truth(source));
+
+    assert_eq!(
+        failure("Boolean subclass: Maybe [ ]").message,
+        "cannot subclass Boolean"
+    );
+}
+
+#[test]
+fn returned_receivers_survive() {
+    let source = "
+        Object subclass: Counter [
+            | n |
+            start [ n := 0 ]
+            bump [ n := n + 1 ]
+            n [ ^n ]
+            fresh [ | c | c := Counter new. c start. ^c ]
+        ].
+        (Counter new fresh bump; bump; yourself) n";
+    assert_eq!(int(source), 2);
+}
+
+#[test]
+fn arrays() {
+    assert_eq!(int("#(1 2 3) size"), 3);
+    assert_eq!(
+        int("| a | a := Array new: 3. a at: 2 put: 5. (a at: 2) + a size"),
+        8
+    );
+    assert_eq!(
+        int("| s | s := 0. {1. 2 + 3. 4} do: [:e | s := s + e]. s"),
+        10
+    );
+    assert_eq!(failure("#(1 2) at: 3").message, "index 3 out of bounds");
+}
+
+#[test]
+fn bags() {
+    assert_eq!(
+        int("| b | b := Bag new. b at: #a put: 3; at: #b put: 4. (b at: #a) + b size"),
+        5
+    );
+    assert!(truth("(Bag new at: #missing) isNil"));
+    assert!(truth(
+        "| b | b := Bag new. b at: #a put: 1. (b includesKey: #a) & (b includesKey: #b) not"
+    ));
+    assert_eq!(failure("Bag new at: 1").message, "argument is not a Symbol");
+}
+
+#[test]
+fn memory_stats() {
+    assert_eq!(int("Smalltalk memoryStats size"), 16);
+    assert!(truth("(Smalltalk memoryStats at: #strongs) > 0"));
+    assert!(truth(
+        "| s | s := Smalltalk memoryStats. (s at: #localAllocated) >= (s at: #localFree)"
+    ));
+    assert!(truth(
+        "| n | n := Smalltalk memoryStats at: #symbols.
+        ('fresh' , n printString) asSymbol.
+        (Smalltalk memoryStats at: #symbols) > n"
+    ));
+    assert!(truth("(Smalltalk memoryStats at: #symbolBytes) > 0"));
+    assert!(truth("Smalltalk class == SystemDictionary"));
+}
+
+#[test]
+fn strings() {
+    assert_eq!(int("'hello' size"), 5);
+    assert!(truth("('ab' , 'cd') = 'abcd'"));
+    assert!(truth("'abc' asSymbol = #abc asString asSymbol"));
+    assert!(truth("'abc' ~= #abc"));
+    assert!(truth("String new size = 0"));
+
+    let mut interp = Interpreter::with_engine(Engine::Tree);
+    let result = evaluate(&mut interp, "'it''s'").unwrap();
+    assert_eq!(interp.print_string(&result), "'it''s'");
+}
+
+#[test]
+fn escaping_blocks_own_their_context() {
+    let source = "
+        | add c |
+        Object subclass: Maker [
+            adder: n [ ^[:x | x + n] ]
+            counter [ | n | n := 0. ^[n := n + 1] ]
+        ].
+        add := Maker new adder: 10.
+        c := Maker new counter.
+        c value. c value.
+        (add value: 5) + c value";
+    assert_eq!(int(source), 18);
+
+    let source = "
+        Object subclass: Maker [ escape [ ^[:x | ^x] ] ].
+        (Maker new escape) value: 3";
+    assert_eq!(
+        failure(source).message,
+        "block cannot return, its home method has returned"
+    );
+}
+
+#[test]
+fn value_with_arguments() {
+    assert_eq!(int("[:a :b | a - b] valueWithArguments: #(10 3)"), 7);
+    assert_eq!(int("[7] valueWithArguments: #()"), 7);
+    assert_eq!(
+        failure("[:a | a] valueWithArguments: #(1 2)").message,
+        "block expects 1 arguments, got 2"
+    );
+    assert_eq!(
+        failure("[:a | a] valueWithArguments: 3").message,
+        "argument is not an Array"
+    );
+}
+
+#[test]
+fn bytecode_compilation() {
+    use super::bytecode::Op;
+
+    let code = compiler::compile_body(&syntax::parse("| a | a := 3 + 4. a").unwrap());
+    assert_eq!(
+        code.ops,
+        vec![
+            Op::PushLiteral(0),
+            Op::PushLiteral(1),
+            Op::Send(0, 1),
+            Op::StoreTemp(0, 0),
+            Op::Pop,
+            Op::PushTemp(0, 0),
+            Op::ReturnTop,
+        ]
+    );
+
+    let code = compiler::compile_body(&syntax::parse("x > 0 ifTrue: [1] ifFalse: [2]").unwrap());
+    assert!(code.ops.contains(&Op::Branch(0)));
+    assert!(!code.ops.iter().any(|op| matches!(op, Op::MakeBlock(_))));
+    assert_eq!(code.blocks.len(), 2);
+}
+
+#[test]
+fn inlined_conditionals_fall_back_to_sends() {
+    let source = "
+        Object subclass: Maybe [ ifTrue: a ifFalse: b [ ^b value ] ].
+        Maybe new ifTrue: [1] ifFalse: [2]";
+    assert_eq!(int(source), 2);
+    assert_eq!(failure("[#a] whileTrue: [4]").message, "not a Boolean");
+    assert_eq!(int("| b | b := [1]. true ifTrue: b"), 1);
+}
+
+#[test]
+fn interactive_bindings() {
+    for engine in [Engine::Tree, Engine::Bytecode] {
+        let mut interp = Interpreter::with_engine(engine);
+        assert_eq!(
+            evaluate(&mut interp, "x := 3").err().unwrap().message,
+            "assignment to undeclared variable x"
+        );
+
+        interp.auto_declare = true;
+        evaluate(&mut interp, "x := 3").ok().unwrap();
+        interp.declare("y");
+        evaluate(&mut interp, "y := x + 1").ok().unwrap();
+        let result = evaluate(&mut interp, "x * y").ok().unwrap();
+        assert_eq!(result.as_int(), Some(12));
+    }
+}
+
+#[test]
+fn display_uses_print_string() {
+    let mut interp = Interpreter::with_engine(Engine::Tree);
+    let result = evaluate(&mut interp, "#(1 #a 'b' nil true)").ok().unwrap();
+    assert_eq!(interp.display(result).unwrap(), "#(1 #a 'b' nil true)");
+
+    let source = "
+        Object subclass: Point [ printString [ ^'a point' ] ].
+        Point new";
+    let result = evaluate(&mut interp, source).ok().unwrap();
+    assert_eq!(interp.display(result).unwrap(), "a point");
+}
+
+#[test]
+fn copies() {
+    assert_eq!(
+        int("| a b | a := #(1 2 3). b := a copy. b at: 1 put: 10. (a at: 1) + (b at: 1)"),
+        11
+    );
+    assert!(truth("| a | a := 'abc'. (a copy = a) & (a copy ~~ a)"));
+    assert!(truth(
+        "(#abc copy = #abc) & (true copy == true) & (3 copy = 3)"
+    ));
+
+    let source = "
+        | p q |
+        Object subclass: Pair [
+            | left right |
+            init [ left := #(1 2). right := 3 ]
+            left [ ^left ]
+        ].
+        p := Pair new init.
+        q := p copy.
+        q left at: 1 put: 5.
+        (p left at: 1) * 10 + (q left at: 1)";
+    assert_eq!(int(source), 15);
+
+    let source = "Object subclass: M [ make [ | n | n := 1. ^[n] ] ]. M new make copy";
+    assert_eq!(failure(source).message, "cannot copy a BlockClosure");
+}
+
+#[test]
+fn owners_move_into_fields() {
+    let class = "
+        Object subclass: Box [
+            | items |
+            fill [ | a | a := {1. 2. 3}. items := a. ^a size ]
+            items: arr [ items := arr ]
+            items [ ^items ]
+        ]";
+    assert_eq!(
+        int(&format!(
+            "| b | {class}. b := Box new. b fill + b items size"
+        )),
+        6
+    );
+    assert_eq!(
+        int(&format!(
+            "| b | {class}. b := Box new. b items: {{4. 5}}. b items size"
+        )),
+        2
+    );
+
+    let source =
+        format!("| a b | {class}. a := {{1}}. b := Box new. b items: a. a := nil. b items size");
+    assert!(failure(&source).message.starts_with("dangling reference"));
+}
+
+#[test]
+fn owners_move_into_stores() {
+    let class = "
+        Object subclass: Box [
+            | items |
+            fill: arr [ | a | a := {7}. arr at: 1 put: a. ^a size ]
+            keep [ | b | b := Bag new. items := b. b at: #x put: {1. 2}. ^self ]
+            stash [ | a | a := {1. 2}. items at: #y put: a ]
+            items [ ^items ]
+        ]";
+    assert_eq!(
+        int(&format!(
+            "| b arr | {class}. arr := Array new: 1. b := Box new. (b fill: arr) + (arr at: 1) size"
+        )),
+        2
+    );
+    assert_eq!(
+        int(&format!(
+            "| b | {class}. b := Box new keep. b stash. ((b items) at: #y) size"
+        )),
+        2
+    );
+}
+
+#[test]
+fn ambiguous_moves() {
+    let source = "
+        Object subclass: Pair [
+            | l r |
+            fill: f [ | a | a := 'x'. f ifTrue: [l := a]. r := a ]
+        ]";
+    assert_eq!(diagnostics(source), [("ambiguous move of a".into(), "a")]);
+    let span = infer_body(&syntax::parse(source).unwrap()).unwrap_err()[0].span;
+    assert_eq!(span.start, source.rfind('a').unwrap());
+
+    let source = "Object subclass: P [ |l| f [ | a | a := {1}. [l isNil] whileTrue: [l := a] ] ]";
+    assert_eq!(diagnostics(source), [("ambiguous move of a".into(), "a")]);
+
+    let source = "| a b | a := {1}. b := {2}. [G := a. H := b]. G := a. H := b";
+    assert_eq!(
+        diagnostics(source),
+        [
+            ("ambiguous move of a".into(), "a"),
+            ("ambiguous move of b".into(), "b")
+        ]
+    );
+
+    let source = "| a | a := {1}. a isNil ifTrue: [G := a]. Smalltalk at: #k put: a";
+    assert_eq!(diagnostics(source), [("ambiguous move of a".into(), "a")]);
+}
+
+#[test]
+fn unambiguous_moves() {
+    let source = "
+        | p |
+        Object subclass: Pair [
+            | l r |
+            fill: f [ | a | a := 'x'. f ifTrue: [l := a] ifFalse: [r := a]. ^l ]
+            both: x [ l := x. r := x. ^r ]
+            twice [ | a | a := 'yz'. l := a. r := a. ^r ]
+        ].
+        p := Pair new.
+        (p fill: true) size + (p both: 'abc') size + p twice size";
+    assert_eq!(diagnostics(source), []);
+    assert_eq!(int(source), 6);
+
+    let source = "| a | a := #(1). [Smalltalk := a]";
+    assert_eq!(diagnostics(source), []);
+    let source = "
+        | a b arr |
+        arr := Array new: 1.
+        a := {1. 2}.
+        b := [arr at: 1 put: a].
+        b value. b value.
+        (arr at: 1) size";
+    assert_eq!(int(source), 2);
+}
+
+#[test]
+fn ownership_inference() {
+    use super::bytecode::Op;
+
+    let source = "| a b | a := {1}. b := 3. G := a. [:x | H := b. K := x]";
+    let body = infer_body(&syntax::parse(source).unwrap()).unwrap();
+    let code = compiler::compile_body(&body);
+    assert!(code.ops.contains(&Op::MoveTemp(0, 0)));
+    let ops = &code.blocks[0].1.ops;
+    assert!(ops.contains(&Op::PushTemp(1, 1)));
+    assert!(ops.contains(&Op::MoveTemp(0, 0)));
+}
@@ -0,0 +1,185 @@
+use super::bytecode::{Code, Op};
+use super::*;
+
+fn pop(stack: &mut Vec<Slot>) -> Slot {
+    stack.pop().expect("operand stack underflow")
+}
+
+impl Interpreter {
+    pub(crate) fn execute(&mut self, context: &Strong<Context>, code: &Code) -> Outcome {
+        let mut stack = Vec::new();
+        let mut pc = 0;
+        loop {
+            let at = pc;
+            pc += 1;
+            match self.step(context, code, code.ops[at], &mut stack, &mut pc) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                Err(err) => {
+                    return Err(match code.spans[at] {
+                        Some(span) => err.at(span),
+                        None => err,
+                    })
+                }
+            }
+        }
+    }
+
+    fn step(
+        &mut self,
+        context: &Strong<Context>,
+        code: &Code,
+        op: Op,
+        stack: &mut Vec<Slot>,
+        pc: &mut usize,
+    ) -> Result<Option<Slot>, Unwind> {
+        match op {
+            Op::PushLiteral(index) => stack.push(self.literal(&code.literals[index])?),
+            Op::PushSelf => stack.push(self.receiver(context)?),
+            Op::PushTemp(depth, index) => {
+                let outer = self.outer_context(context, depth)?;
+                stack.push(self.read_context(&outer)?.get(index).alias());
+            }
+            Op::MoveTemp(depth, index) => {
+                let outer = self.outer_context(context, depth)?;
+                match outer.try_write() {
+                    Ok(mut outer) => stack.push(outer.take(index)),
+                    Err(err) => return context_error(err),
+                }
+            }
+            Op::StoreTemp(depth, index) => {
+                let value = pop(stack);
+                stack.push(value.alias());
+                let outer = self.outer_context(context, depth)?;
+                let old = match outer.try_write() {
+                    Ok(mut outer) => outer.set(index, value),
+                    Err(err) => return context_error(err),
+                };
+                drop(old);
+            }
+            Op::PushField(index) => {
+                let receiver = self.receiver(context)?;
+                let field = self.with_object(&receiver, |o| {
+                    o.as_record().map(|fields| fields[index].alias())
+                })?;
+                stack.push(field.unwrap_or_else(Slot::nil));
+            }
+            Op::StoreField(index) => {
+                let value = pop(stack);
+                stack.push(value.alias());
+                let receiver = self.receiver(context)?;
+                let old = self.with_object_mut(&receiver, |o| {
+                    o.as_record_mut()
+                        .map(|fields| store(&mut fields[index], value))
+                })?;
+                if old.is_none() {
+                    return error("not a record");
+                }
+            }
+            Op::PushGlobal(index) => {
+                let name = code.symbols[index];
+                match self.globals.get(name) {
+                    Some(global) => stack.push(global.alias()),
+                    None => return error(format!("undefined variable {name}")),
+                }
+            }
+            Op::StoreGlobal(index) => {
+                let name = code.symbols[index];
+                let value = pop(stack);
+                stack.push(value.alias());
+                drop(self.assign_global(name, value)?);
+            }
+            Op::Send(selector, argc) => {
+                let arguments = stack.split_off(stack.len() - argc);
+                let receiver = pop(stack);
+                stack.push(self.send(receiver, code.symbols[selector], arguments)?);
+            }
+            Op::SuperSend(selector, argc) => {
+                let arguments = stack.split_off(stack.len() - argc);
+                let receiver = pop(stack);
+                let class = self.method_class(context)?;
+                let Some(superclass) = class.superclass else {
+                    return error(format!("{} has no superclass", class.name));
+                };
+                let result = self.dispatch(superclass, receiver, code.symbols[selector], arguments);
+                stack.push(result?);
+            }
+            Op::MakeBlock(index) => stack.push(self.make_block(context, code, index)?),
+            Op::MakeArray(size) => {
+                let elements = stack.split_off(stack.len() - size);
+                stack.push(Strong::new(Object::array(elements)).into());
+            }
+            Op::DefineClass(index) => stack.push(self.define_class(&code.classes[index])?),
+            Op::Dup => {
+                let top = stack.last().expect("operand stack underflow").alias();
+                stack.push(top);
+            }
+            Op::Pop => drop(pop(stack)),
+            Op::Cascade => {
+                let result = pop(stack);
+                let receiver = pop(stack);
+                match receiver.is_strong() && identical(&receiver, &result) {
+                    true => stack.push(receiver),
+                    false => stack.push(result),
+                }
+            }
+            Op::Jump(target) => *pc = target,
+            Op::JumpIf(when, target) => {
+                let condition = pop(stack);
+                if self.truth(&condition)? == when {
+                    *pc = target;
+                }
+            }
+            Op::Branch(index) => {
+                let branch = &code.branches[index];
+                let condition = pop(stack);
+                match self.with_object(&condition, Object::as_boolean) {
+                    Ok(Some(true)) => {}
+                    Ok(Some(false)) => *pc = branch.otherwise,
+                    _ => {
+                        let mut arguments = Vec::with_capacity(branch.blocks.len());
+                        for &block in &branch.blocks {
+                            arguments.push(self.make_block(context, code, block)?);
+                        }
+                        let selector = code.symbols[branch.selector];
+                        stack.push(self.send(condition, selector, arguments)?);
+                        *pc = branch.end;
+                    }
+                }
+            }
+            Op::Return => {
+                let value = pop(stack);
+                let home = self.read_context(&context.alias())?.home;
+                if !self.homes.contains(&home) {
+                    return error("block cannot return, its home method has returned");
+                }
+                return Err(Unwind::Return(value, home));
+            }
+            Op::ReturnTop => return Ok(Some(pop(stack))),
+        }
+        Ok(None)
+    }
+
+    fn make_block(&self, context: &Strong<Context>, code: &Code, index: usize) -> Outcome {
+        let home = self.read_context(&context.alias())?.home;
+        let (block, body) = &code.blocks[index];
+        let mut procedure = Procedure::new(block.clone(), context.alias(), home);
+        procedure.code = Some(body.clone());
+        Ok(Strong::new(Object::procedure(procedure)).into())
+    }
+
+    fn outer_context(
+        &self,
+        context: &Strong<Context>,
+        depth: usize,
+    ) -> Result<Weak<Context>, Unwind> {
+        let mut current = context.alias();
+        for _ in 0..depth {
+            match self.read_context(&current)?.outer {
+                Some(outer) => current = outer,
+                None => return error("reference to a dead context"),
+            }
+        }
+        Ok(current)
+    }
+}
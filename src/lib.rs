@@ -0,0 +1,7 @@
+//! The aloxtalk interpreter and the generational-reference memory model
+//! behind it.
+
+pub mod interp;
+pub mod memory;
+mod object;
+pub mod syntax;
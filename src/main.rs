@@ -1,12 +1,178 @@
-#![feature(local_key_cell_methods)]
-#![feature(assert_matches)]
-#![feature(half_open_range_patterns)]
-#![feature(exclusive_range_pattern)]
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::{env, fs, process::ExitCode};
 
-mod memory;
-mod object;
+use aloxtalk::interp::{infer_body, Diagnostic, Engine, Interpreter, RuntimeError};
+use aloxtalk::syntax::{self, ParseError};
+
+#[allow(dead_code)]
 mod pipe;
 
-fn main() {
-    println!("Hello, world!");
+const USAGE: &str = "usage: aloxtalk [--bytecode] [script.st | -e expression]";
+
+fn main() -> ExitCode {
+    let mut engine = Engine::Tree;
+    let mut script = None;
+    let mut expression = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bytecode" => engine = Engine::Bytecode,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            "-e" if expression.is_none() => match args.next() {
+                Some(source) => expression = Some(source),
+                None => return usage(),
+            },
+            _ if !arg.starts_with('-') && script.is_none() => script = Some(arg),
+            _ => return usage(),
+        }
+    }
+
+    let mut interp = Interpreter::with_engine(engine);
+    match (script, expression) {
+        (Some(path), None) => match fs::read_to_string(&path) {
+            Ok(source) => run(&mut interp, &path, &source),
+            Err(err) => {
+                eprintln!("error: {path}: {err}");
+                ExitCode::FAILURE
+            }
+        },
+        (None, Some(source)) => run(&mut interp, "-e", &source),
+        (None, None) => repl(&mut interp),
+        (Some(_), Some(_)) => usage(),
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::FAILURE
+}
+
+fn run(interp: &mut Interpreter, origin: &str, source: &str) -> ExitCode {
+    let body = match syntax::parse(source) {
+        Ok(body) => body,
+        Err(err) => {
+            eprintln!("{}", parse_error(origin, &err));
+            return ExitCode::FAILURE;
+        }
+    };
+    let body = match infer_body(&body) {
+        Ok(body) => body,
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}", ownership_error(origin, source, diagnostic));
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+    match evaluate(interp, origin, source, &body) {
+        Ok(printed) => {
+            println!("{printed}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn repl(interp: &mut Interpreter) -> ExitCode {
+    interp.auto_declare = true;
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut lines = stdin.lock().lines();
+    let mut pending = String::new();
+    loop {
+        if interactive {
+            print!("{}", if pending.is_empty() { "> " } else { ". " });
+            let _ = io::stdout().flush();
+        }
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(err)) => {
+                eprintln!("error: {err}");
+                return ExitCode::FAILURE;
+            }
+            None => break,
+        };
+        pending.push_str(&line);
+        pending.push('\n');
+        if pending.trim().is_empty() {
+            pending.clear();
+            continue;
+        }
+
+        let mut body = match syntax::parse(&pending) {
+            Ok(body) => body,
+            Err(err) if err.location.offset >= pending.trim_end().len() => continue,
+            Err(err) => {
+                eprintln!("{}", parse_error("<repl>", &err));
+                pending.clear();
+                continue;
+            }
+        };
+        for name in body.temporaries.drain(..) {
+            interp.declare(&name);
+        }
+        let body = match infer_body(&body) {
+            Ok(body) => body,
+            Err(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", ownership_error("<repl>", &pending, diagnostic));
+                }
+                pending.clear();
+                continue;
+            }
+        };
+        match evaluate(interp, "<repl>", &pending, &body) {
+            Ok(printed) => println!("{printed}"),
+            Err(err) => eprintln!("{err}"),
+        }
+        pending.clear();
+    }
+
+    if !pending.trim().is_empty() {
+        if let Err(err) = syntax::parse(&pending) {
+            eprintln!("{}", parse_error("<repl>", &err));
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn evaluate(
+    interp: &mut Interpreter,
+    origin: &str,
+    source: &str,
+    body: &syntax::Body,
+) -> Result<String, String> {
+    interp
+        .evaluate(body)
+        .map_err(|err| runtime_error(origin, source, &err))
+}
+
+fn parse_error(origin: &str, err: &ParseError) -> String {
+    let location = &err.location;
+    format!(
+        "{origin}:{}:{}: parse error: {err}",
+        location.line, location.column
+    )
+}
+
+fn ownership_error(origin: &str, source: &str, diagnostic: &Diagnostic) -> String {
+    let (line, column) = diagnostic.span.line_col(source);
+    format!("{origin}:{line}:{column}: ownership error: {diagnostic}")
+}
+
+fn runtime_error(origin: &str, source: &str, err: &RuntimeError) -> String {
+    match err.span {
+        Some(span) => {
+            let (line, column) = span.line_col(source);
+            format!("{origin}:{line}:{column}: error: {err}")
+        }
+        None => format!("{origin}: error: {err}"),
+    }
 }
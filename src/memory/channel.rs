@@ -0,0 +1,264 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::{Strong, Transferrable, Weak};
+
+pub enum Received<T: ?Sized + 'static> {
+    Moved(Strong<T>),
+    Shared(Weak<T>),
+}
+
+impl<T: ?Sized + 'static> From<Transferrable<T>> for Received<T> {
+    fn from(it: Transferrable<T>) -> Self {
+        match it {
+            Transferrable::Send(sending) => Self::Moved(Strong::from(sending)),
+            Transferrable::Sync(sharing) => Self::Shared(Weak::from(sharing)),
+        }
+    }
+}
+
+pub struct Sender<T: ?Sized + 'static>(Arc<Channel<T>>);
+
+pub struct Receiver<T: ?Sized + 'static>(Arc<Channel<T>>);
+
+/// A channel that promotes references on the way in and rebuilds them on
+/// the way out. [`Sender::send`] moves ownership, [`Sender::share`] hands
+/// over an alias.
+///
+/// ```
+/// use std::thread;
+///
+/// use aloxtalk::memory::{bounded, select, Received, Strong};
+///
+/// let (jobs, queue) = bounded::<u64>(4);
+/// let (urgent, express) = bounded::<u64>(4);
+///
+/// let worker = thread::spawn(move || {
+///     let mut total = 0;
+///     while let Ok((_, job)) = select(&[&express, &queue]) {
+///         if let Received::Moved(job) = job {
+///             total += *job.try_read().unwrap();
+///         }
+///     }
+///     total
+/// });
+///
+/// for n in 1..=3 {
+///     jobs.send(Strong::new(n)).ok().unwrap();
+/// }
+/// urgent.send(Strong::new(10)).ok().unwrap();
+/// drop((jobs, urgent));
+/// assert_eq!(worker.join().unwrap(), 16);
+/// ```
+pub fn channel<T: ?Sized + 'static>() -> (Sender<T>, Receiver<T>) {
+    Channel::open(None)
+}
+
+pub fn bounded<T: ?Sized + 'static>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "bounded channels need room for one message");
+    Channel::open(Some(capacity))
+}
+
+pub fn select<T: ?Sized + 'static>(
+    receivers: &[&Receiver<T>],
+) -> Result<(usize, Received<T>), RecvError> {
+    select_until(receivers, None).map_err(|_| RecvError)
+}
+
+pub fn select_timeout<T: ?Sized + 'static>(
+    receivers: &[&Receiver<T>],
+    timeout: Duration,
+) -> Result<(usize, Received<T>), RecvTimeoutError> {
+    select_until(receivers, Some(Instant::now() + timeout))
+}
+
+struct Channel<T: ?Sized + 'static> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+}
+
+struct State<T: ?Sized + 'static> {
+    queue: VecDeque<Transferrable<T>>,
+    capacity: Option<usize>,
+    senders: usize,
+    receiving: bool,
+    waiting: Vec<Arc<Signal>>,
+}
+
+#[derive(Default)]
+struct Signal {
+    ready: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn notify(&self) {
+        *self.ready.lock() = true;
+        self.condvar.notify_one();
+    }
+
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let mut ready = self.ready.lock();
+        while !*ready {
+            match deadline {
+                Some(deadline) => {
+                    if self.condvar.wait_until(&mut ready, deadline).timed_out() {
+                        return *ready;
+                    }
+                }
+                None => self.condvar.wait(&mut ready),
+            }
+        }
+        *ready = false;
+        true
+    }
+}
+
+impl<T: ?Sized + 'static> Channel<T> {
+    fn open(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                capacity,
+                senders: 1,
+                receiving: true,
+                waiting: Vec::new(),
+            }),
+            not_full: Condvar::new(),
+        });
+        (Sender(channel.clone()), Receiver(channel))
+    }
+
+    fn push(&self, item: Transferrable<T>) -> Result<(), Transferrable<T>> {
+        let mut state = self.state.lock();
+        while state.receiving && state.capacity.is_some_and(|c| state.queue.len() >= c) {
+            self.not_full.wait(&mut state);
+        }
+        if !state.receiving {
+            return Err(item);
+        }
+        state.queue.push_back(item);
+        let waiting = std::mem::take(&mut state.waiting);
+        drop(state);
+        waiting.iter().for_each(|signal| signal.notify());
+        Ok(())
+    }
+}
+
+fn select_until<T: ?Sized + 'static>(
+    receivers: &[&Receiver<T>],
+    deadline: Option<Instant>,
+) -> Result<(usize, Received<T>), RecvTimeoutError> {
+    let signal = Arc::new(Signal::default());
+    let res = loop {
+        let mut connected = false;
+        let mut received = None;
+        for (i, receiver) in receivers.iter().enumerate() {
+            let mut state = receiver.0.state.lock();
+            if let Some(item) = state.queue.pop_front() {
+                drop(state);
+                receiver.0.not_full.notify_one();
+                received = Some((i, item));
+                break;
+            }
+            if state.senders > 0 {
+                connected = true;
+                if !state.waiting.iter().any(|s| Arc::ptr_eq(s, &signal)) {
+                    state.waiting.push(signal.clone());
+                }
+            }
+        }
+        match received {
+            Some((i, item)) => break Ok((i, item.into())),
+            None if !connected => break Err(RecvTimeoutError::Disconnected),
+            None if !signal.wait(deadline) => break Err(RecvTimeoutError::Timeout),
+            None => {}
+        }
+    };
+    for receiver in receivers {
+        let mut state = receiver.0.state.lock();
+        state.waiting.retain(|s| !Arc::ptr_eq(s, &signal));
+    }
+    res
+}
+
+impl<T: ?Sized + Send + Sync + 'static> Sender<T> {
+    pub fn send(&self, it: Strong<T>) -> Result<(), SendError<Strong<T>>> {
+        self.0
+            .push(Transferrable::Send(it.send()))
+            .map_err(|it| match it {
+                Transferrable::Send(sending) => SendError(Strong::from(sending)),
+                Transferrable::Sync(_) => unreachable!(),
+            })
+    }
+}
+
+impl<T: ?Sized + Sync + 'static> Sender<T> {
+    pub fn share(&self, it: Weak<T>) -> Result<(), SendError<Weak<T>>> {
+        self.0
+            .push(Transferrable::Sync(it.share()))
+            .map_err(|_| SendError(it))
+    }
+}
+
+impl<T: ?Sized + 'static> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.0.state.lock().senders += 1;
+        Self(self.0.clone())
+    }
+}
+
+impl<T: ?Sized + 'static> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            let waiting = std::mem::take(&mut state.waiting);
+            drop(state);
+            waiting.iter().for_each(|signal| signal.notify());
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Receiver<T> {
+    pub fn recv(&self) -> Result<Received<T>, RecvError> {
+        select(&[self]).map(|(_, it)| it)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Received<T>, RecvTimeoutError> {
+        select_timeout(&[self], timeout).map(|(_, it)| it)
+    }
+
+    pub fn try_recv(&self) -> Result<Received<T>, TryRecvError> {
+        let mut state = self.0.state.lock();
+        match state.queue.pop_front() {
+            Some(item) => {
+                drop(state);
+                self.0.not_full.notify_one();
+                Ok(item.into())
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let queue = {
+            let mut state = self.0.state.lock();
+            state.receiving = false;
+            std::mem::take(&mut state.queue)
+        };
+        self.0.not_full.notify_all();
+        drop(queue);
+    }
+}
@@ -1,12 +1,23 @@
 use std::{
+    alloc::{self, Layout},
     cell::{Cell, RefCell},
-    ptr::NonNull,
-    sync::atomic::{AtomicU32, AtomicUsize, Ordering::*},
+    collections::HashSet,
+    mem::{self, MaybeUninit},
+    ops::{Deref, Range},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{fence, AtomicPtr, AtomicU8, AtomicUsize, Ordering::*},
+        Arc,
+    },
+    thread,
+    time::Duration,
 };
 
-use lock_api::{RawRwLock, RawRwLockDowngrade, RawRwLockUpgrade};
+use lock_api::{RawRwLock, RawRwLockDowngrade, RawRwLockTimed, RawRwLockUpgrade};
 use parking_lot::Mutex;
 
+use super::{AccessError, CounterStats, Plain};
+
 pub(crate) trait Generation: Sized {
     fn free(this: Self);
 }
@@ -25,43 +36,167 @@ impl LocalGeneration {
     }
 
     fn fresh() -> Self {
-        let mut fresh = Self::FRESH_LIST.with(|c| c.replace(vec![].into_boxed_slice()));
-        let mut next = Self::NEXT_FRESH.with(Cell::get);
+        Self::FRESH_LIST.with(|fresh| loop {
+            let mut slice = fresh.slice.take();
+            let mut next = fresh.next.get();
+
+            if next == slice.len() {
+                Self::LEAKED_COUNTER_SLICES.with(|leaked| leaked.insert(slice));
+                slice = (0..next + next / 2).map(|_| LocalCounter::new()).collect();
+                fresh
+                    .vacated
+                    .borrow()
+                    .restart(&slice, LocalCounter::restart);
+                Self::ALLOCATED_COUNTERS.with(|c| c.set(c.get() + next + next / 2));
+                next = 0;
+            }
+
+            let res = Self(NonNull::from(&slice[next]));
+            fresh.slice.set(slice);
+            fresh.next.set(next + 1);
+            if res.count() != 0 {
+                break res;
+            }
+        })
+    }
+
+    fn re_use() -> Option<Self> {
+        Self::FREE_LIST.with(|v| v.0.borrow_mut().pop())
+    }
+
+    fn retire_slabs() -> usize {
+        Self::LEAKED_COUNTER_SLICES.with(|leaked| {
+            Self::FREE_LIST.with(|free| {
+                let mut free = free.0.borrow_mut();
+                let free_set: HashSet<_> = free.iter().map(|g| g.0.as_ptr().cast_const()).collect();
+                let (dead, live): (Vec<_>, Vec<_>) =
+                    leaked.0.take().into_iter().partition(|slab| {
+                        slab.iter()
+                            .all(|c| c.unused() || free_set.contains(&ptr::from_ref(c)))
+                    });
+                *leaked.0.borrow_mut() = live;
+                if dead.is_empty() {
+                    return 0;
+                }
 
-        if next == fresh.len() {
-            Self::LEAKED_COUNTER_SLICES.with_borrow_mut(|v| v.push(fresh));
-            fresh = (0..next + next / 2).map(|_| LocalCounter::new()).collect();
-            Self::ALLOCATED_COUNTERS.with(|c| c.set(c.get() + next + next / 2));
-            next = 0;
+                free.retain(|g| {
+                    !dead
+                        .iter()
+                        .any(|slab| slab.as_ptr_range().contains(&g.0.as_ptr().cast_const()))
+                });
+                Self::FRESH_LIST.with(|fresh| {
+                    let mut vacated = fresh.vacated.borrow_mut();
+                    for slab in &dead {
+                        vacated.record(slab, |c| c.count());
+                    }
+                });
+                for counter in dead.iter().flat_map(|slab| slab.iter()) {
+                    counter.unbind();
+                }
+                Self::RELEASED.set(true);
+                let released = dead.iter().map(|slab| slab.len()).sum::<usize>();
+                Self::ALLOCATED_COUNTERS.with(|c| c.set(c.get() - released));
+                released
+            })
+        })
+    }
+
+    // Parked slabs without live counters. Their thread is gone, and stale
+    // aliases from other threads only touch parked counters under the lock.
+    fn exhume() -> usize {
+        let mut graveyard = GRAVEYARD.lock();
+        let (dead, parked): (Vec<_>, Vec<_>) = graveyard.drain(..).partition(|p| p.live == 0);
+        *graveyard = parked;
+        for counter in dead.iter().flat_map(|parked| parked.counters.iter()) {
+            counter.unbind();
         }
+        dead.iter().map(|parked| parked.counters.len()).sum()
+    }
 
-        let res = Self(NonNull::from(&fresh[next]));
-        next += 1;
-        Self::FRESH_LIST.with(|c| c.set(fresh));
-        Self::NEXT_FRESH.with(|c| c.set(next));
-        res
+    // Once this thread released or parked a slab, its aliases may point
+    // into memory that is gone and look their slab up first.
+    fn reach<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        if !Self::RELEASED.get() {
+            return Some(f());
+        }
+        let address = self.0.as_ptr() as usize;
+        let own = Self::FRESH_LIST
+            .try_with(|fresh| {
+                let slice = fresh.slice.take();
+                let res = holds(&slice, address);
+                fresh.slice.set(slice);
+                res
+            })
+            .unwrap_or(false)
+            || Self::LEAKED_COUNTER_SLICES
+                .try_with(|leaked| search(&leaked.0.borrow(), address))
+                .unwrap_or(false);
+        if own {
+            return Some(f());
+        }
+        let graveyard = GRAVEYARD.lock();
+        graveyard
+            .iter()
+            .any(|parked| holds(&parked.counters, address))
+            .then(f)
     }
 
-    fn re_use() -> Option<Self> {
-        Self::FREE_LIST.with_borrow_mut(Vec::pop)
+    // Retires a counter during thread teardown. Its slab may be parked
+    // already, then it has one live counter less.
+    fn bury(self) {
+        let mut graveyard = GRAVEYARD.lock();
+        unsafe { self.0.as_ref() }.retire();
+        let address = self.0.as_ptr() as usize;
+        if let Some(parked) = graveyard.iter_mut().find(|p| holds(&p.counters, address)) {
+            parked.live -= 1;
+        }
     }
 
     thread_local! {
-        static FREE_LIST : RefCell<Vec<LocalGeneration>> = RefCell::new(Vec::new());
-        static NEXT_FRESH : Cell<usize> = Cell::new(0);
-        static FRESH_LIST : Cell<Box<[LocalCounter]>> = Cell::new((0..32).map(|_| LocalCounter::new()).collect());
-        static ALLOCATED_COUNTERS : Cell<usize> = Cell::new(0);
-        static LEAKED_COUNTER_SLICES : RefCell<Vec<Box<[LocalCounter]>>> = RefCell::new(Vec::new());
+        static FREE_LIST : FreeCounters = const { FreeCounters(RefCell::new(Vec::new())) };
+        static FRESH_LIST : FreshCounters = FreshCounters::new(32);
+        static ALLOCATED_COUNTERS : Cell<usize> = const { Cell::new(0) };
+        static LEAKED_COUNTER_SLICES : LeakedCounters = const { LeakedCounters(RefCell::new(Vec::new())) };
+        static RELEASED : Cell<bool> = const { Cell::new(false) };
     }
 
-    #[allow(dead_code)]
     pub(crate) fn allocations() -> usize {
         Self::ALLOCATED_COUNTERS.with(Cell::get)
     }
 
-    #[allow(dead_code)]
     pub(crate) fn free_list_size() -> usize {
-        Self::FREE_LIST.with_borrow(Vec::len)
+        Self::FREE_LIST.with(|v| v.0.borrow().len())
+    }
+
+    pub(crate) fn stats() -> CounterStats {
+        let (retired, globalized) = Self::FRESH_LIST.with(|fresh| {
+            let slice = fresh.slice.take();
+            let issued = slice[..fresh.next.get()].iter();
+            let stats = Self::LEAKED_COUNTER_SLICES.with(|leaked| {
+                let leaked = leaked.0.borrow();
+                let counters = issued.chain(leaked.iter().flat_map(|slab| slab.iter()));
+                counters.fold((0, 0), |(retired, globalized), c| {
+                    (
+                        retired + (c.count() == 0) as usize,
+                        globalized + c.is_globalized() as usize,
+                    )
+                })
+            });
+            fresh.slice.set(slice);
+            stats
+        });
+        let allocated = Self::allocations();
+        CounterStats {
+            allocated,
+            free: Self::free_list_size(),
+            retired,
+            globalized,
+            slab_bytes: allocated * mem::size_of::<LocalCounter>(),
+        }
+    }
+
+    pub(crate) fn parked() -> usize {
+        GRAVEYARD.lock().iter().map(|p| p.counters.len()).sum()
     }
 
     #[inline(always)]
@@ -77,69 +212,602 @@ impl LocalGeneration {
 
 impl Generation for LocalGeneration {
     fn free(this: Self) {
-        if this.count() != 0 {
-            Self::FREE_LIST.with_borrow_mut(|v| v.push(this))
+        // the free list is gone during thread teardown, nobody can reuse the counter
+        if Self::FREE_LIST
+            .try_with(|v| {
+                if this.count() != 0 {
+                    v.0.borrow_mut().push(this)
+                }
+            })
+            .is_err()
+        {
+            this.bury();
+        }
+    }
+}
+
+// at thread exit free and unissued counters are retired and the slices
+// parked in the graveyard until none of their counters is live
+struct FreeCounters(RefCell<Vec<LocalGeneration>>);
+
+impl Drop for FreeCounters {
+    fn drop(&mut self) {
+        for gen in self.0.take() {
+            gen.bury();
+        }
+    }
+}
+
+struct FreshCounters {
+    slice: Cell<Box<[LocalCounter]>>,
+    next: Cell<usize>,
+    vacated: RefCell<Vacated>,
+}
+
+impl FreshCounters {
+    fn new(size: usize) -> Self {
+        LocalGeneration::ALLOCATED_COUNTERS.with(|c| c.set(c.get() + size));
+        Self {
+            slice: Cell::new((0..size).map(|_| LocalCounter::new()).collect()),
+            next: Cell::new(0),
+            vacated: RefCell::new(Vacated::default()),
+        }
+    }
+}
+
+impl Drop for FreshCounters {
+    fn drop(&mut self) {
+        let slice = self.slice.take();
+        for counter in &slice[self.next.get()..] {
+            counter.retire();
+        }
+        park(slice);
+    }
+}
+
+// sorted by address, so aliases find their slab quickly
+struct LeakedCounters(RefCell<Vec<Box<[LocalCounter]>>>);
+
+impl LeakedCounters {
+    fn insert(&self, slice: Box<[LocalCounter]>) {
+        let mut leaked = self.0.borrow_mut();
+        let at = leaked.partition_point(|s| s.as_ptr() < slice.as_ptr());
+        leaked.insert(at, slice);
+    }
+}
+
+impl Drop for LeakedCounters {
+    fn drop(&mut self) {
+        for slice in self.0.take() {
+            park(slice);
+        }
+    }
+}
+
+fn park(slice: Box<[LocalCounter]>) {
+    LocalGeneration::RELEASED.set(true);
+    if !slice.is_empty() {
+        let mut graveyard = GRAVEYARD.lock();
+        let live = slice.iter().filter(|c| !c.unused()).count();
+        graveyard.push(Parked {
+            counters: slice,
+            live,
+        });
+    }
+}
+
+// A slab of an exited thread. Whatever still touches its counters does so
+// under the graveyard lock or holds one of the `live` ones, so reclaim only
+// goes by the count and never reads the cells.
+struct Parked {
+    counters: Box<[LocalCounter]>,
+    live: usize,
+}
+
+unsafe impl Send for Parked {}
+
+fn holds<C>(slab: &[C], address: usize) -> bool {
+    let start = slab.as_ptr() as usize;
+    let offset = address.wrapping_sub(start);
+    offset < mem::size_of_val(slab) && offset.is_multiple_of(mem::size_of::<C>())
+}
+
+fn search<S: Deref<Target = [C]>, C>(slabs: &[S], address: usize) -> bool {
+    let at = slabs.partition_point(|slab| slab.as_ptr() as usize <= address);
+    at > 0 && holds(&slabs[at - 1], address)
+}
+
+// Where released slabs were, and which generation their counters had
+// reached. Stale aliases into them hold at most that generation, or any
+// generation if their counter was retired, so counters placed there later
+// start above it or retired.
+#[derive(Default)]
+struct Vacated {
+    floors: Vec<(Range<usize>, Count)>,
+    retired: HashSet<usize>,
+}
+
+impl Vacated {
+    fn record<C>(&mut self, slab: &[C], count: impl Fn(&C) -> Count) {
+        let mut floor = COUNTER_INIT;
+        for counter in slab {
+            match count(counter) {
+                0 | Count::MAX => {
+                    self.retired.insert(ptr::from_ref(counter) as usize);
+                }
+                n => floor = floor.max(n + 1),
+            }
+        }
+        let range = slab.as_ptr_range();
+        self.floors
+            .push((range.start as usize..range.end as usize, floor));
+    }
+
+    fn restart<C>(&self, slab: &[C], start: impl Fn(&C, Count)) {
+        if self.floors.is_empty() {
+            return;
+        }
+        let (base, size) = (slab.as_ptr() as usize, mem::size_of::<C>());
+        let mut starts = vec![COUNTER_INIT; slab.len()];
+        for (range, floor) in &self.floors {
+            let first = range.start.saturating_sub(base).div_ceil(size);
+            let last = range.end.saturating_sub(base).div_ceil(size);
+            for n in &mut starts[first.min(slab.len())..last.min(slab.len())] {
+                *n = (*n).max(*floor);
+            }
+        }
+        for (i, (counter, n)) in slab.iter().zip(starts).enumerate() {
+            if self.retired.contains(&(base + i * size)) {
+                start(counter, 0);
+            } else if n != COUNTER_INIT {
+                start(counter, n);
+            }
+        }
+    }
+}
+
+// Grace periods for global slabs. A thread pins the epoch while it looks
+// at a global counter, and memory retired at some epoch is released once
+// the epoch moved on twice: every thread pinned at the time has let go by
+// then. Threads that cannot pin any more during their own teardown keep
+// the epoch from moving at all.
+
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+static UNREGISTERED_PINS: AtomicUsize = AtomicUsize::new(0);
+
+const COLLECT_AT: usize = 64;
+const COLLECT_ATTEMPTS: usize = 64;
+
+struct Participant {
+    // `epoch << 1 | 1` while pinned, 0 otherwise
+    state: Arc<AtomicUsize>,
+    pins: Cell<usize>,
+}
+
+impl Participant {
+    fn register() -> Self {
+        let state = Arc::new(AtomicUsize::new(0));
+        PARTICIPANTS.lock().push(state.clone());
+        Self {
+            state,
+            pins: Cell::new(0),
         }
     }
 }
 
+impl Drop for Participant {
+    fn drop(&mut self) {
+        PARTICIPANTS.lock().retain(|p| !Arc::ptr_eq(p, &self.state));
+    }
+}
+
+thread_local! {
+    static PARTICIPANT : Participant = Participant::register();
+}
+
+struct Pin {
+    registered: bool,
+}
+
+fn pin() -> Pin {
+    let registered = PARTICIPANT
+        .try_with(|p| {
+            let pins = p.pins.get();
+            p.pins.set(pins + 1);
+            if pins == 0 {
+                p.state.store(EPOCH.load(Relaxed) << 1 | 1, Relaxed);
+                fence(SeqCst);
+            }
+        })
+        .is_ok();
+    if !registered {
+        UNREGISTERED_PINS.fetch_add(1, SeqCst);
+    }
+    Pin { registered }
+}
+
+impl Drop for Pin {
+    fn drop(&mut self) {
+        if !self.registered {
+            UNREGISTERED_PINS.fetch_sub(1, Release);
+            return;
+        }
+        let _ = PARTICIPANT.try_with(|p| {
+            let pins = p.pins.get() - 1;
+            p.pins.set(pins);
+            if pins == 0 {
+                p.state.store(0, Release);
+            }
+        });
+    }
+}
+
+fn try_advance() {
+    let epoch = EPOCH.load(Relaxed);
+    fence(SeqCst);
+    let lagging = UNREGISTERED_PINS.load(Relaxed) != 0
+        || PARTICIPANTS.lock().iter().any(|p| {
+            let state = p.load(Relaxed);
+            state & 1 == 1 && state >> 1 != epoch
+        });
+    if !lagging {
+        fence(Acquire);
+        let _ = EPOCH.compare_exchange(epoch, epoch + 1, Release, Relaxed);
+    }
+}
+
+enum Garbage {
+    Counters(&'static [GlobalCounter]),
+    // a table that was swapped out of `LIVE_SLABS`
+    Slabs(*mut Vec<&'static [GlobalCounter]>),
+    Allocation(NonNull<u8>, Layout),
+}
+
+unsafe impl Send for Garbage {}
+
+impl Garbage {
+    // A thread that went to sleep on one of the locks is still inside the
+    // slab, so that slab waits for the next round.
+    unsafe fn release(self) -> Result<usize, Self> {
+        match self {
+            Self::Counters(slab) if slab.iter().any(|c| c.waiters.load(Acquire) != 0) => Err(self),
+            Self::Counters(slab) => {
+                let len = slab.len();
+                drop(Box::from_raw(ptr::from_ref(slab).cast_mut()));
+                Ok(len)
+            }
+            Self::Slabs(slabs) => {
+                drop(Box::from_raw(slabs));
+                Ok(0)
+            }
+            Self::Allocation(pointer, layout) => {
+                alloc::dealloc(pointer.as_ptr(), layout);
+                Ok(0)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Retired {
+    garbage: Vec<(usize, Garbage)>,
+    collect_at: usize,
+}
+
+fn defer(garbage: Garbage) {
+    fence(SeqCst);
+    let epoch = EPOCH.load(Relaxed);
+    let full = {
+        let mut retired = RETIRED.lock();
+        retired.garbage.push((epoch, garbage));
+        retired.garbage.len() >= retired.collect_at
+    };
+    if full {
+        collect();
+    }
+}
+
+/// # Safety
+/// The memory must have been allocated with `layout` by the global
+/// allocator, and nothing but optimistic readers may touch it any more.
+pub(crate) unsafe fn defer_dealloc(pointer: NonNull<u8>, layout: Layout) {
+    defer(Garbage::Allocation(pointer, layout));
+}
+
+// Waits until every thread that was pinned when this was called let go.
+pub(crate) fn synchronize() {
+    let start = EPOCH.load(Acquire);
+    while EPOCH.load(Acquire).wrapping_sub(start) < 2 {
+        try_advance();
+        thread::yield_now();
+    }
+}
+
+// Releases what no pinned thread can still be looking at and returns the
+// number of counters released.
+fn collect() -> usize {
+    try_advance();
+    try_advance();
+    let epoch = EPOCH.load(Acquire);
+    let mut retired = RETIRED.lock();
+    let mut released = 0;
+    for (at, garbage) in mem::take(&mut retired.garbage) {
+        let garbage = match epoch.wrapping_sub(at) >= 2 {
+            true => match unsafe { garbage.release() } {
+                Ok(n) => {
+                    released += n;
+                    continue;
+                }
+                Err(garbage) => garbage,
+            },
+            false => garbage,
+        };
+        retired.garbage.push((at, garbage));
+    }
+    retired.collect_at = COLLECT_AT.max(retired.garbage.len() * 2);
+    released
+}
+
+static GLOBALIZED: AtomicUsize = AtomicUsize::new(0);
+
+/// Frees counter slabs that no reference can reach any more and returns the
+/// number of counters released.
+///
+/// A slab qualifies once all its counters are free or retired. `Weak` is
+/// `Copy`, so stale aliases into a released slab stay around: they check
+/// that their slab still exists before touching the counter, and global
+/// slabs are only handed back after every thread that might be in the
+/// middle of such a check has moved on. Counters placed where released ones
+/// lived start above any generation a stale alias can hold.
+pub fn reclaim() -> usize {
+    let mut released = LocalGeneration::retire_slabs() + LocalGeneration::exhume();
+    for garbage in GlobalGeneration::retire_slabs() {
+        defer(garbage);
+    }
+    for _ in 0..COLLECT_ATTEMPTS {
+        released += collect();
+        let pending = RETIRED
+            .lock()
+            .garbage
+            .iter()
+            .any(|(_, garbage)| matches!(garbage, Garbage::Counters(_)));
+        if !pending {
+            break;
+        }
+        thread::yield_now();
+    }
+    released
+}
+
+// Global slabs that are neither released nor handed to reclaim, for stale
+// global aliases to check against. Null until the first release; before
+// that no global counter has gone away.
+static LIVE_SLABS: AtomicPtr<Vec<&'static [GlobalCounter]>> = AtomicPtr::new(ptr::null_mut());
+
+fn publish(slabs: &[&'static [GlobalCounter]]) -> Option<Garbage> {
+    let old = LIVE_SLABS.swap(Box::into_raw(Box::new(slabs.to_vec())), AcqRel);
+    (!old.is_null()).then_some(Garbage::Slabs(old))
+}
+
+// Stands in for the counter of a stale alias that is promoted after its
+// slab was released.
+static DANGLING: GlobalCounter = GlobalCounter::retired();
+
+const REUSE_SCAN: usize = 8;
+
 #[repr(transparent)]
 #[derive(Clone, Copy)]
 pub(crate) struct GlobalGeneration(pub(crate) &'static GlobalCounter);
 
 impl GlobalGeneration {
-    #[allow(dead_code)]
+    #[cfg(test)]
     pub(crate) fn new() -> Self {
-        Self::re_use().unwrap_or_else(Self::fresh)
+        Self::re_use(Count::MAX).unwrap_or_else(Self::fresh)
+    }
+
+    pub(crate) fn dangling() -> Self {
+        Self(&DANGLING)
     }
 
     fn fresh() -> Self {
+        let mut garbage = None;
         let mut fresh = FRESH_LIST.lock();
+        let res = loop {
+            if fresh.1.is_empty() {
+                fresh.1 = Box::leak(
+                    (0..fresh.0)
+                        .map(|_| GlobalCounter::new())
+                        .collect::<Box<[GlobalCounter]>>(),
+                );
+                VACATED
+                    .lock()
+                    .restart(fresh.1, |c, n| c.counter.store(n, Relaxed));
+                GLOBAL_SLABS.lock().push(fresh.1);
+                let mut table = GLOBAL_TABLE.lock();
+                let at = table.partition_point(|slab| slab.as_ptr() < fresh.1.as_ptr());
+                table.insert(at, fresh.1);
+                if !LIVE_SLABS.load(Relaxed).is_null() {
+                    garbage = publish(&table);
+                }
+
+                ALLOCATED_COUNTERS.fetch_add(fresh.0, Relaxed);
+                fresh.0 += fresh.0 / 2;
+            }
 
-        if fresh.1.len() == 0 {
-            fresh.1 = (0..fresh.0)
-                .map(|_| GlobalCounter::new())
-                .collect::<Vec<GlobalCounter>>()
-                .leak();
+            let res = Self(&fresh.1[0]);
+            fresh.1 = &fresh.1[1..];
+            if res.count() != 0 {
+                break res;
+            }
+        };
+        drop(fresh);
+        if let Some(garbage) = garbage {
+            defer(garbage);
+        }
+        res
+    }
+
+    // Aliases of a promoted local counter keep their generation, so the
+    // global counter must not be past it already.
+    fn re_use(count: Count) -> Option<Self> {
+        let mut free = FREE_LIST.lock();
+        let scan = free.len().saturating_sub(REUSE_SCAN);
+        let at = scan + free[scan..].iter().rposition(|g| g.count() <= count)?;
+        Some(free.swap_remove(at))
+    }
 
-            ALLOCATED_COUNTERS.fetch_add(fresh.0, Relaxed);
-            fresh.0 += fresh.0 / 2;
+    fn reach<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        let _pin = pin();
+        let slabs = unsafe { LIVE_SLABS.load(Acquire).as_ref() };
+        match slabs {
+            Some(slabs) if !search(slabs, ptr::from_ref(self.0) as usize) => None,
+            _ => Some(f()),
         }
+    }
 
-        let res = Self(&fresh.1[0]);
-        fresh.1 = &fresh.1[1..];
+    pub(crate) fn lock_shared_for(&self, timeout: Option<Duration>) -> bool {
+        match timeout {
+            Some(timeout) => self.0.access.try_lock_shared_for(timeout),
+            None => {
+                self.0.access.lock_shared();
+                true
+            }
+        }
+    }
+
+    pub(crate) fn lock_exclusive_for(&self, timeout: Option<Duration>) -> bool {
+        let res = match timeout {
+            Some(timeout) => self.0.access.try_lock_exclusive_for(timeout),
+            None => {
+                self.0.access.lock_exclusive();
+                true
+            }
+        };
+        if res {
+            self.0.begin_write();
+        }
         res
     }
 
-    fn re_use() -> Option<Self> {
-        FREE_LIST.lock().pop()
+    // Seqlock-style read that writes nothing shared. Writers and the final
+    // free both hold the exclusive lock, which keeps `seq` odd, so a value
+    // read while `seq` stayed even and the generation stayed valid is not
+    // torn. The copy may still race a writer, so it goes through atomic
+    // loads into `MaybeUninit` and is only trusted once validated. The free
+    // path defers releasing shared allocations past the pin.
+    pub(crate) unsafe fn read_optimistic<T: Plain>(
+        &self,
+        validity: Count,
+        pointer: NonNull<T>,
+    ) -> Option<T> {
+        self.reach(|| {
+            let seq = self.0.seq.load(Acquire);
+            if seq & 1 == 1 || self.0.count() != validity {
+                return None;
+            }
+            let value = atomic_copy(pointer);
+            fence(Acquire);
+            (self.0.seq.load(Relaxed) == seq && self.0.count() == validity)
+                .then(|| value.assume_init())
+        })
+        .flatten()
     }
 
     fn from_local(rlc: RawLocalCounter) -> Self {
-        let this = Self::fresh();
+        let this = Self::re_use(rlc.count()).unwrap_or_else(Self::fresh);
+        GLOBALIZED.fetch_add(1, Relaxed);
 
         this.0.set_gen(rlc.count());
+        // the local counter forwards here until its slab is released
+        this.0.forwards.fetch_add(1, Relaxed);
 
-        rlc.access_state().inflict(&this.0.access);
+        rlc.access_state().inflict(this.0);
 
         this
     }
 
-    #[allow(dead_code)]
+    // The local counter this one was promoted from lets go of its object.
+    // If that thread has exited, its parked slab has one live counter less.
+    fn detach(self) {
+        let mut graveyard = GRAVEYARD.lock();
+        let origin = self.0.origin.swap(0, Relaxed);
+        if let Some(parked) = graveyard.iter_mut().find(|p| holds(&p.counters, origin)) {
+            parked.live -= 1;
+        }
+    }
+
     pub(crate) fn allocations() -> usize {
         ALLOCATED_COUNTERS.load(Relaxed)
     }
 
-    #[allow(dead_code)]
     pub(crate) fn free_list_size() -> usize {
         FREE_LIST.lock().len()
     }
 
+    #[cfg(test)]
     pub(crate) fn leak_all_and_reset() {
         let mut x = FREE_LIST.lock();
         let mut y = FRESH_LIST.lock();
         *x = Vec::new();
         *y = (32, &[]);
+        GLOBAL_SLABS.lock().clear();
+    }
+
+    pub(crate) fn stats() -> CounterStats {
+        let allocated = Self::allocations();
+        let retired = GLOBAL_SLABS
+            .lock()
+            .iter()
+            .flat_map(|slab| slab.iter())
+            .filter(|c| c.count() == 0)
+            .count();
+        CounterStats {
+            allocated,
+            free: Self::free_list_size(),
+            retired,
+            globalized: GLOBALIZED.load(Relaxed),
+            slab_bytes: allocated * mem::size_of::<GlobalCounter>(),
+        }
+    }
+
+    // Takes the slabs whose counters are all free or retired out of use.
+    // They are only released after a grace period, see `reclaim`.
+    fn retire_slabs() -> Vec<Garbage> {
+        let mut free = FREE_LIST.lock();
+        let fresh = FRESH_LIST.lock();
+        let mut slabs = GLOBAL_SLABS.lock();
+
+        let free_set: HashSet<_> = free.iter().map(|g| ptr::from_ref(g.0)).collect();
+        let (dead, live): (Vec<_>, Vec<_>) = slabs.drain(..).partition(|slab| {
+            !slab.as_ptr_range().contains(&fresh.1.as_ptr())
+                && slab.iter().all(|c| {
+                    c.forwards.load(Acquire) == 0
+                        && c.waiters.load(Relaxed) == 0
+                        && (c.unused() || free_set.contains(&ptr::from_ref(c)))
+                })
+        });
+        *slabs = live;
+        if dead.is_empty() {
+            return Vec::new();
+        }
+
+        free.retain(|g| {
+            !dead
+                .iter()
+                .any(|slab| slab.as_ptr_range().contains(&ptr::from_ref(g.0)))
+        });
+        let mut vacated = VACATED.lock();
+        for slab in &dead {
+            vacated.record(slab, |c| c.count());
+        }
+        let mut table = GLOBAL_TABLE.lock();
+        table.retain(|slab| !dead.iter().any(|d| ptr::eq(*slab, *d)));
+        let released = dead.iter().map(|slab| slab.len()).sum();
+        ALLOCATED_COUNTERS.fetch_sub(released, Relaxed);
+
+        let mut garbage: Vec<_> = publish(&table).into_iter().collect();
+        garbage.extend(dead.into_iter().map(Garbage::Counters));
+        garbage
     }
 
     #[inline(always)]
@@ -154,13 +822,22 @@ impl GlobalGeneration {
 }
 
 lazy_static::lazy_static! {
+    static ref GRAVEYARD : Mutex<Vec<Parked>> = Mutex::new(Vec::new());
+    static ref GLOBAL_SLABS : Mutex<Vec<&'static [GlobalCounter]>> = Mutex::new(Vec::new());
+    static ref GLOBAL_TABLE : Mutex<Vec<&'static [GlobalCounter]>> = Mutex::new(Vec::new());
     static ref FREE_LIST : Mutex<Vec<GlobalGeneration>> = Mutex::new(Vec::new());
     static ref FRESH_LIST : Mutex<(usize, &'static [GlobalCounter])> = Mutex::new((32, &[]));
+    static ref VACATED : Mutex<Vacated> = Mutex::new(Vacated::default());
     static ref ALLOCATED_COUNTERS : AtomicUsize = AtomicUsize::new(0);
+    static ref PARTICIPANTS : Mutex<Vec<Arc<AtomicUsize>>> = Mutex::new(Vec::new());
+    static ref RETIRED : Mutex<Retired> = Mutex::new(Retired::default());
 }
 
 impl Generation for GlobalGeneration {
     fn free(this: Self) {
+        if this.0.origin.load(Relaxed) != 0 {
+            this.detach();
+        }
         if this.count() != 0 {
             FREE_LIST.lock().push(this)
         }
@@ -174,6 +851,91 @@ pub(crate) enum LocalOrGlobalGeneration {
 }
 
 impl LocalOrGlobalGeneration {
+    pub(crate) fn address(&self) -> usize {
+        match self {
+            Self::Local(l) => l.0.as_ptr() as usize,
+            Self::Global(g) => ptr::from_ref(g.0) as usize,
+        }
+    }
+
+    // A globalized counter answers with the local counter it was promoted
+    // from, so aliases taken before and after globalization agree.
+    pub(crate) fn identity(&self) -> usize {
+        match self {
+            Self::Local(_) => self.address(),
+            Self::Global(g) => match g.reach(|| g.0.origin.load(Relaxed)) {
+                None | Some(0) => self.address(),
+                Some(origin) => origin,
+            },
+        }
+    }
+
+    // Runs `f` while the counter cannot go away under it, or returns `None`
+    // if its slab has been released already.
+    pub(crate) fn reach<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        match self {
+            Self::Local(l) => l.reach(f),
+            Self::Global(g) => g.reach(f),
+        }
+    }
+
+    // A thread about to block on the lock leaves the grace period, so it
+    // keeps the slab from being released instead.
+    pub(crate) fn enqueue(&self) {
+        if let Self::Global(g) = self {
+            g.0.waiters.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub(crate) fn dequeue(&self) {
+        if let Self::Global(g) = self {
+            g.0.waiters.fetch_sub(1, Release);
+        }
+    }
+
+    // Other threads may read the object without a lock, see
+    // `read_optimistic`.
+    pub(crate) fn is_shared(&self) -> bool {
+        match self {
+            Self::Local(l) => unsafe { l.0.as_ref() }.is_globalized(),
+            Self::Global(_) => true,
+        }
+    }
+
+    pub(crate) fn lock_shared_for(&self, timeout: Option<Duration>) -> bool {
+        match self {
+            Self::Local(l) => l.try_lock_shared(),
+            Self::Global(g) => g.lock_shared_for(timeout),
+        }
+    }
+
+    pub(crate) fn lock_exclusive_for(&self, timeout: Option<Duration>) -> bool {
+        match self {
+            Self::Local(l) => l.try_lock_exclusive(),
+            Self::Global(g) => g.lock_exclusive_for(timeout),
+        }
+    }
+
+    pub(crate) fn access_error(&self) -> AccessError {
+        match self {
+            Self::Local(l) => match unsafe { l.0.as_ref() }.delegate(|log| match log {
+                LocalOrGlobalCounter::Local(raw) => Some(raw.access_state()),
+                _ => None,
+            }) {
+                Some(AccessState::Writer) => AccessError::WriteLocked,
+                Some(AccessState::Readers { normal, upgrade }) => AccessError::ReadLocked {
+                    readers: Some(normal as usize + upgrade as usize),
+                },
+                Some(AccessState::None) => AccessError::ReadLocked { readers: Some(0) },
+                None => AccessError::Globalized,
+            },
+            Self::Global(g) => match g.0.access.is_locked_exclusive() {
+                true => AccessError::WriteLocked,
+                false => AccessError::ReadLocked { readers: None },
+            },
+        }
+    }
+
     #[inline(always)]
     fn delegate<R>(&self, fl: fn(&LocalGeneration) -> R, fg: fn(&GlobalGeneration) -> R) -> R {
         match self {
@@ -212,10 +974,78 @@ impl LocalCounter {
         Self(Cell::new(LocalOrGlobalCounter::new()))
     }
 
+    fn address(&self) -> usize {
+        ptr::from_ref(self) as usize
+    }
+
+    fn restart(&self, count: Count) {
+        let log = self.0.replace(LocalOrGlobalCounter::Placeholder);
+        if let LocalOrGlobalCounter::Local(raw) = &log {
+            raw.counter.set(count);
+        }
+        self.0.set(log);
+    }
+
+    fn is_globalized(&self) -> bool {
+        self.delegate(|log| matches!(log, LocalOrGlobalCounter::Global(_)))
+    }
+
+    fn global(&self) -> Option<GlobalGeneration> {
+        self.delegate(|log| match log {
+            LocalOrGlobalCounter::Global(g) => Some(*g),
+            _ => None,
+        })
+    }
+
+    // No reference can get at the object any more: the counter is retired
+    // and unlocked, or the global counter it forwards to has let go of it.
+    fn unused(&self) -> bool {
+        match self.global() {
+            Some(g) => g.0.origin.load(Relaxed) != self.address() || g.0.unused(),
+            None => self.delegate(|log| {
+                matches!(log, LocalOrGlobalCounter::Local(raw)
+                    if raw.count() == 0 && raw.access.get() == 0)
+            }),
+        }
+    }
+
+    fn retire(&self) {
+        match self.global() {
+            Some(g) => {
+                if g.0.origin.load(Relaxed) == self.address() {
+                    g.0.origin.store(0, Relaxed);
+                    if g.count() != 0 {
+                        FREE_LIST.lock().push(g);
+                    }
+                }
+            }
+            None => self.delegate(|log| {
+                if let LocalOrGlobalCounter::Local(raw) = log {
+                    raw.counter.set(0)
+                }
+            }),
+        }
+    }
+
+    // The slab is going away, the global counter no longer has this one
+    // forwarding to it.
+    fn unbind(&self) {
+        if let Some(g) = self.global() {
+            self.retire();
+            g.0.forwards.fetch_sub(1, Release);
+        }
+    }
+
     pub(crate) fn globalize(&self) -> GlobalGeneration {
         let res = match self.0.replace(LocalOrGlobalCounter::Placeholder) {
             LocalOrGlobalCounter::Placeholder => panic!(),
-            LocalOrGlobalCounter::Local(l) => GlobalGeneration::from_local(l),
+            LocalOrGlobalCounter::Local(l) => {
+                let global = GlobalGeneration::from_local(l);
+                global.0.origin.store(self.address(), Relaxed);
+                #[cfg(feature = "debug-refs")]
+                super::debug::globalized(self.address(), ptr::from_ref(global.0) as usize);
+                global
+            }
             LocalOrGlobalCounter::Global(g) => g,
         };
         self.0.set(LocalOrGlobalCounter::Global(res));
@@ -227,7 +1057,7 @@ impl LocalCounter {
         let log = self.0.replace(LocalOrGlobalCounter::Placeholder);
         let res = f(&log);
         self.0.set(log);
-        return res;
+        res
     }
 
     #[inline(always)]
@@ -235,7 +1065,7 @@ impl LocalCounter {
         let log = self.0.replace(LocalOrGlobalCounter::Placeholder);
         let res = f(&log);
         self.0.set(log);
-        return res;
+        res
     }
 }
 
@@ -273,22 +1103,83 @@ impl LocalOrGlobalCounter {
     }
 }
 
-pub(crate) const COUNTER_INIT: u32 = 1;
+// Copies `T` with relaxed atomic loads, word by word where the alignment
+// allows it, so a racing writer can tear the copy but not make it a data race.
+unsafe fn atomic_copy<T: Plain>(src: NonNull<T>) -> MaybeUninit<T> {
+    let mut dst = MaybeUninit::<T>::uninit();
+    let (src, out) = (src.as_ptr().cast::<u8>(), dst.as_mut_ptr().cast::<u8>());
+    let mut offset = 0;
+    if mem::align_of::<T>() >= mem::align_of::<usize>() {
+        while offset + mem::size_of::<usize>() <= mem::size_of::<T>() {
+            let word = (*src.add(offset).cast::<AtomicUsize>()).load(Relaxed);
+            out.add(offset).cast::<usize>().write(word);
+            offset += mem::size_of::<usize>();
+        }
+    }
+    while offset < mem::size_of::<T>() {
+        out.add(offset)
+            .write((*src.add(offset).cast::<AtomicU8>()).load(Relaxed));
+        offset += 1;
+    }
+    dst
+}
+
+#[cfg(not(feature = "wide-generations"))]
+pub(crate) type Count = u32;
+#[cfg(not(feature = "wide-generations"))]
+type AtomicCount = std::sync::atomic::AtomicU32;
+
+#[cfg(feature = "wide-generations")]
+pub(crate) type Count = u64;
+#[cfg(feature = "wide-generations")]
+type AtomicCount = std::sync::atomic::AtomicU64;
+
+pub(crate) const COUNTER_INIT: Count = 1;
 
 pub(crate) struct GlobalCounter {
     pub(crate) access: parking_lot::RawRwLock,
-    pub(crate) counter: AtomicU32,
+    pub(crate) counter: AtomicCount,
+    pub(crate) seq: AtomicUsize,
+    origin: AtomicUsize,
+    // local counters that forward here, and threads asleep on the lock
+    forwards: AtomicUsize,
+    waiters: AtomicUsize,
 }
 
 impl GlobalCounter {
-    pub(crate) fn new() -> Self {
+    pub(crate) const fn new() -> Self {
+        Self::starting_at(COUNTER_INIT)
+    }
+
+    const fn retired() -> Self {
+        Self::starting_at(0)
+    }
+
+    const fn starting_at(count: Count) -> Self {
         Self {
             access: parking_lot::RawRwLock::INIT,
-            counter: AtomicU32::new(COUNTER_INIT),
+            counter: AtomicCount::new(count),
+            seq: AtomicUsize::new(0),
+            origin: AtomicUsize::new(0),
+            forwards: AtomicUsize::new(0),
+            waiters: AtomicUsize::new(0),
         }
     }
 
-    fn set_gen(&self, gen: u32) -> bool {
+    fn unused(&self) -> bool {
+        self.count() == 0 && !self.access.is_locked()
+    }
+
+    fn begin_write(&self) {
+        self.seq.fetch_add(1, Relaxed);
+        fence(Release);
+    }
+
+    fn end_write(&self) {
+        self.seq.fetch_add(1, Release);
+    }
+
+    fn set_gen(&self, gen: Count) -> bool {
         loop {
             let n = self.counter.load(Relaxed);
             if gen <= n {
@@ -317,7 +1208,7 @@ impl GlobalCounter {
 
 pub(crate) struct RawLocalCounter {
     pub(crate) access: Cell<i32>,
-    pub(crate) counter: Cell<u32>,
+    pub(crate) counter: Cell<Count>,
 }
 
 impl RawLocalCounter {
@@ -352,8 +1243,9 @@ impl AccessState {
         }
     }
 
-    fn inflict(&self, access: &parking_lot::RawRwLock) {
+    fn inflict(&self, counter: &GlobalCounter) {
         use AccessState::*;
+        let access = &counter.access;
         match self {
             Readers { normal, upgrade } => {
                 for _ in 0..*normal {
@@ -363,7 +1255,10 @@ impl AccessState {
                     access.lock_upgradable()
                 }
             }
-            Writer => access.lock_exclusive(),
+            Writer => {
+                access.lock_exclusive();
+                counter.begin_write();
+            }
             None => {}
         }
     }
@@ -387,7 +1282,7 @@ pub(crate) trait AccessControl {
 
 pub(crate) trait GenerationCounter {
     fn bump(&self);
-    fn count(&self) -> u32;
+    fn count(&self) -> Count;
 }
 
 impl GenerationCounter for RawLocalCounter {
@@ -399,7 +1294,7 @@ impl GenerationCounter for RawLocalCounter {
         }
     }
 
-    fn count(&self) -> u32 {
+    fn count(&self) -> Count {
         self.counter.get()
     }
 }
@@ -504,7 +1399,7 @@ impl GenerationCounter for GlobalCounter {
         }
     }
 
-    fn count(&self) -> u32 {
+    fn count(&self) -> Count {
         self.counter.load(Relaxed)
     }
 }
@@ -514,20 +1409,43 @@ impl AccessControl for GlobalCounter {
     //     self.access.try_lock_shared_recursive()
     // }
     delegate!(fn try_lock_shared -> bool, parking_lot::RawRwLock);
-    delegate!(fn try_lock_exclusive -> bool, parking_lot::RawRwLock);
     delegate!(fn try_lock_upgradable -> bool, parking_lot::RawRwLock);
-    delegate!(unsafe fn downgrade -> (), parking_lot::RawRwLock);
     //delegate!(unsafe fn downgrade_upgradable -> (), parking_lot::RawRwLock);
     //delegate!(unsafe fn downgrade_to_upgradable -> (), parking_lot::RawRwLock);
-    delegate!(unsafe fn try_upgrade -> bool, parking_lot::RawRwLock);
     delegate!(unsafe fn unlock_shared -> (), parking_lot::RawRwLock);
     delegate!(unsafe fn unlock_upgradable -> (), parking_lot::RawRwLock);
-    delegate!(unsafe fn unlock_exclusive -> (), parking_lot::RawRwLock);
+
+    fn try_lock_exclusive(&self) -> bool {
+        let res = self.access.try_lock_exclusive();
+        if res {
+            self.begin_write();
+        }
+        res
+    }
+
+    unsafe fn downgrade(&self) {
+        self.end_write();
+        self.access.downgrade()
+    }
+
+    unsafe fn try_upgrade(&self) -> bool {
+        let res = self.access.try_upgrade();
+        if res {
+            self.begin_write();
+        }
+        res
+    }
+
+    unsafe fn unlock_exclusive(&self) {
+        self.end_write();
+        self.access.unlock_exclusive()
+    }
 
     unsafe fn try_shared_into_exclusive(&self) -> bool {
         if self.access.try_lock_upgradable() {
             self.access.unlock_shared();
             if self.access.try_upgrade() {
+                self.begin_write();
                 return true;
             }
             if !self.access.try_lock_shared() {
@@ -543,7 +1461,7 @@ macro_rules! delegate_all {
     ($it:ty : use $($sub:ty),+) => {
         impl GenerationCounter for $it {
             delegate!(fn bump -> (), $($sub),+);
-            delegate!(fn count -> u32, $($sub),+);
+            delegate!(fn count -> Count, $($sub),+);
         }
 
         impl AccessControl for $it {
@@ -0,0 +1,113 @@
+use std::{backtrace::Backtrace, collections::HashMap, fmt, sync::Arc};
+
+use parking_lot::Mutex;
+
+use super::counter::Count;
+
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub generation: Count,
+    pub allocated: Arc<Backtrace>,
+    pub freed: Option<Arc<Backtrace>>,
+}
+
+impl PartialEq for Provenance {
+    fn eq(&self, other: &Self) -> bool {
+        self.generation == other.generation && Arc::ptr_eq(&self.allocated, &other.allocated)
+    }
+}
+
+impl Eq for Provenance {}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "generation {} allocated at:", self.generation)?;
+        write!(f, "{}", self.allocated)?;
+        match &self.freed {
+            Some(freed) => write!(f, "\nfreed at:\n{freed}"),
+            None => write!(f, "\nnot freed"),
+        }
+    }
+}
+
+// Records are keyed by the counter that allocated them. Globalization links
+// the global counter to the same record, so aliases on either side find it.
+struct Record {
+    provenance: Provenance,
+    counters: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Tracker {
+    counters: HashMap<usize, usize>,
+    records: HashMap<usize, Record>,
+}
+
+impl Tracker {
+    fn forget(&mut self, counter: usize) {
+        if let Some(record) = self
+            .counters
+            .remove(&counter)
+            .and_then(|id| self.records.remove(&id))
+        {
+            for counter in record.counters {
+                self.counters.remove(&counter);
+            }
+        }
+    }
+
+    fn get(&mut self, counter: usize) -> Option<&mut Provenance> {
+        let id = self.counters.get(&counter)?;
+        self.records.get_mut(id).map(|r| &mut r.provenance)
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref PROVENANCE : Mutex<Tracker> = Mutex::new(Tracker::default());
+}
+
+pub(crate) fn allocated(counter: usize, generation: Count) {
+    let provenance = Provenance {
+        generation,
+        allocated: Arc::new(Backtrace::force_capture()),
+        freed: None,
+    };
+    let mut tracker = PROVENANCE.lock();
+    tracker.forget(counter);
+    tracker.counters.insert(counter, counter);
+    let counters = vec![counter];
+    tracker.records.insert(
+        counter,
+        Record {
+            provenance,
+            counters,
+        },
+    );
+}
+
+pub(crate) fn globalized(local: usize, global: usize) {
+    let mut tracker = PROVENANCE.lock();
+    tracker.forget(global);
+    if let Some(&id) = tracker.counters.get(&local) {
+        tracker.counters.insert(global, id);
+        if let Some(record) = tracker.records.get_mut(&id) {
+            record.counters.push(global);
+        }
+    }
+}
+
+pub(crate) fn freed(counter: usize, generation: Count) {
+    if let Some(provenance) = PROVENANCE.lock().get(counter) {
+        if provenance.generation == generation {
+            provenance.freed = Some(Arc::new(Backtrace::force_capture()));
+        }
+    }
+}
+
+pub(crate) fn lookup(counter: usize, generation: Count) -> Option<Provenance> {
+    PROVENANCE
+        .lock()
+        .get(counter)
+        .filter(|p| p.generation == generation)
+        .cloned()
+}
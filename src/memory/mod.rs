@@ -1,25 +1,128 @@
+//! Generational references.
+//!
+//! A [`Strong`] owns a heap allocation together with a generation counter.
+//! Any number of [`Weak`] aliases can be copied out of it; they stay valid
+//! while the counter still holds the generation they were created with.
+//! Dropping the `Strong` bumps the counter, so every alias reports
+//! [`AccessError::Dangling`] from then on instead of reading freed memory.
+//! Access goes through guards ([`Reading`], [`Writing`], [`Upgrading`]) that
+//! hold a reader/writer lock on the counter.
+//!
+//! ```
+//! use aloxtalk::memory::{AccessError, Strong};
+//!
+//! let strong = Strong::new(vec![1, 2]);
+//! let weak = strong.alias();
+//!
+//! weak.try_write().unwrap().push(3);
+//! assert_eq!(*strong.try_read().unwrap(), [1, 2, 3]);
+//!
+//! drop(strong);
+//! assert!(matches!(weak.try_read(), Err(AccessError::Dangling { .. })));
+//! ```
+//!
+//! # Moving between threads
+//!
+//! New references use a thread-local counter and cannot leave their thread.
+//! Promoting one to a global counter (`make_sharable`) swaps the counter for
+//! an atomic one and keeps every existing alias valid. Crossing a thread
+//! boundary then goes through one of two tokens:
+//!
+//! * [`Sending`], from [`Strong::send`], moves ownership to another thread,
+//!   which turns it back into a `Strong`.
+//! * [`Sharing`], from [`Weak::share`], hands another thread an alias while
+//!   ownership stays put.
+//!
+//! [`Transferrable`] carries either token, so a single channel can do both.
+//! [`channel()`] and [`bounded`] wrap that pattern and hand out rebuilt
+//! references on the receiving side.
+//!
+//! ```
+//! use std::{sync::mpsc, thread};
+//!
+//! use aloxtalk::memory::{Strong, Transferrable, Weak};
+//!
+//! let (tx, rx) = mpsc::channel::<Transferrable<String>>();
+//! let worker = thread::spawn(move || {
+//!     for message in rx {
+//!         match message {
+//!             Transferrable::Send(sending) => {
+//!                 let owned = Strong::from(sending);
+//!                 owned.try_write().unwrap().push('!');
+//!             }
+//!             Transferrable::Sync(sharing) => {
+//!                 let alias = Weak::from(sharing);
+//!                 alias.write_blocking().unwrap().push('?');
+//!             }
+//!         }
+//!     }
+//! });
+//!
+//! let kept = Strong::new(String::from("kept"));
+//! let alias = kept.alias();
+//! tx.send(Transferrable::Sync(alias.share())).unwrap();
+//! tx.send(Transferrable::Send(Strong::new(String::from("given")).send()))
+//!     .unwrap();
+//! drop(tx);
+//! worker.join().unwrap();
+//!
+//! assert_eq!(*alias.read_blocking().unwrap(), "kept?");
+//! ```
+
 use std::{
+    alloc::Layout,
+    fmt,
+    hash::{Hash, Hasher},
     mem,
     ops::{Deref, DerefMut},
-    ptr::NonNull,
+    ptr::{self, NonNull},
+    sync::atomic::Ordering::Relaxed,
+    time::Duration,
 };
 
+pub mod channel;
 pub(crate) mod counter;
+#[cfg(feature = "debug-refs")]
+pub(crate) mod debug;
 pub(crate) mod pointers;
+pub(crate) mod stats;
 mod tests;
+pub(crate) mod weak_map;
+
+pub use channel::{bounded, channel, select, select_timeout, Received, Receiver, Sender};
+pub use counter::reclaim;
+#[cfg(feature = "debug-refs")]
+pub use debug::Provenance;
+pub use stats::{stats, CounterStats, MemoryStats};
+pub use weak_map::WeakKeyMap;
 
 use counter::*;
 use pointers::*;
 
 #[repr(transparent)]
-pub struct Strong<T: 'static>(RawRef<T>);
+pub struct Strong<T: ?Sized + 'static>(RawRef<T>);
+
+// Drops the object. Optimistic readers on other threads may still be
+// copying out of a shared allocation, so its memory is only handed back
+// after a grace period.
+unsafe fn free_box<T: ?Sized>(gen: LocalOrGlobalGeneration, pointer: NonNull<T>) {
+    if !gen.is_shared() {
+        return mem::drop(Box::from_raw(pointer.as_ptr()));
+    }
+    let layout = Layout::for_value(pointer.as_ref());
+    ptr::drop_in_place(pointer.as_ptr());
+    if layout.size() != 0 {
+        counter::defer_dealloc(pointer.cast(), layout);
+    }
+}
 
-#[allow(dead_code)]
 impl<T: 'static> Strong<T> {
     pub fn new(it: T) -> Self {
         Self::from(Box::new(it))
     }
+}
 
+impl<T: ?Sized + 'static> Strong<T> {
     pub fn alias(&self) -> Weak<T> {
         Weak(self.0)
     }
@@ -27,35 +130,70 @@ impl<T: 'static> Strong<T> {
     pub fn try_take(self) -> Result<Box<T>, Self> {
         let gen = self.0.generation();
         if gen.try_lock_exclusive() {
+            #[cfg(feature = "debug-refs")]
+            debug::freed(gen.address(), self.0.validity());
             gen.bump();
+            let shared = gen.is_shared();
             let res = unsafe { Box::from_raw(self.0.pointer().as_ptr()) };
             unsafe {
                 gen.unlock_exclusive();
             }
             LocalOrGlobalGeneration::free(gen);
+            stats::STRONGS.fetch_sub(1, Relaxed);
             std::mem::forget(self);
+            // optimistic readers may still be copying out of the box
+            if shared {
+                counter::synchronize();
+            }
             Ok(res)
         } else {
             Err(self)
         }
     }
 
-    fn try_read(&self) -> Option<Reading<T>> {
-        if self.0.generation().try_lock_shared() {
-            Some(Reading(self.0))
+    pub fn try_read(&self) -> Result<Reading<T>, AccessError> {
+        let gen = self.0.generation();
+        if gen.try_lock_shared() {
+            Ok(Reading::new(self.0))
         } else {
-            None
+            Err(gen.access_error())
         }
     }
 
-    fn try_write(&self) -> Option<Writing<T>> {
-        if self.0.generation().try_lock_exclusive() {
-            Some(Writing(self.0))
+    pub fn try_write(&self) -> Result<Writing<T>, AccessError> {
+        let gen = self.0.generation();
+        if gen.try_lock_exclusive() {
+            Ok(Writing::new(self.0))
         } else {
-            None
+            Err(gen.access_error())
         }
     }
 
+    pub fn try_read_upgradable(&self) -> Result<Upgrading<T>, AccessError> {
+        let gen = self.0.generation();
+        if gen.try_lock_upgradable() {
+            Ok(Upgrading::new(self.0))
+        } else {
+            Err(gen.access_error())
+        }
+    }
+
+    pub fn read_blocking(&self) -> Result<Reading<T>, AccessError> {
+        Weak(self.0).read_for(None)
+    }
+
+    pub fn write_blocking(&self) -> Result<Writing<T>, AccessError> {
+        Weak(self.0).write_for(None)
+    }
+
+    pub fn read_timeout(&self, timeout: Duration) -> Result<Reading<T>, AccessError> {
+        Weak(self.0).read_for(Some(timeout))
+    }
+
+    pub fn write_timeout(&self, timeout: Duration) -> Result<Writing<T>, AccessError> {
+        Weak(self.0).write_for(Some(timeout))
+    }
+
     pub(crate) fn into_raw(self) -> RawRef<T> {
         let mut res = self.0;
         res.ownership = OwnershipBit::Strong;
@@ -68,8 +206,22 @@ impl<T: 'static> Strong<T> {
     }
 }
 
-#[allow(dead_code)]
-impl<T: Send + Sync + 'static> Strong<T> {
+impl<T: ?Sized + Send + Sync + 'static> Strong<T> {
+    /// Promotes the reference and wraps it for a move to another thread.
+    ///
+    /// ```
+    /// use std::thread;
+    ///
+    /// use aloxtalk::memory::Strong;
+    ///
+    /// let sending = Strong::new(41).send();
+    /// let answer = thread::spawn(move || {
+    ///     let strong = Strong::from(sending);
+    ///     *strong.try_write().unwrap() += 1;
+    ///     *strong.try_take().ok().unwrap()
+    /// });
+    /// assert_eq!(answer.join().unwrap(), 42);
+    /// ```
     pub fn send(mut self) -> Sending<T> {
         self = self.make_sharable();
         if let RawRefEnum::Global(res) = self.0.into() {
@@ -81,8 +233,25 @@ impl<T: Send + Sync + 'static> Strong<T> {
     }
 }
 
-#[allow(dead_code)]
-impl<T: Sync + 'static> Strong<T> {
+impl<T: ?Sized + Sync + 'static> Strong<T> {
+    /// Moves the reference onto a global counter. Aliases taken before the
+    /// promotion stay valid, and so do guards that are still held.
+    ///
+    /// ```
+    /// use aloxtalk::memory::{stats, Strong};
+    ///
+    /// let strong = Strong::new(1);
+    /// let before = strong.alias();
+    /// let reading = before.try_read().unwrap();
+    ///
+    /// let strong = strong.make_sharable();
+    /// assert!(stats().global.allocated > 0);
+    /// assert!(strong.try_write().is_err());
+    ///
+    /// drop(reading);
+    /// *strong.try_write().unwrap() = 2;
+    /// assert_eq!(*before.try_read().unwrap(), 2);
+    /// ```
     pub fn make_sharable(self) -> Self {
         let res = Self(
             match self.0.into() {
@@ -96,30 +265,45 @@ impl<T: Sync + 'static> Strong<T> {
     }
 }
 
-impl<T: 'static> Drop for Strong<T> {
+impl<T: Plain> Strong<T> {
+    pub fn try_read_copy(&self) -> Result<T, AccessError> {
+        Weak(self.0).try_read_copy()
+    }
+}
+
+impl<T: ?Sized + 'static> Drop for Strong<T> {
     fn drop(&mut self) {
+        stats::STRONGS.fetch_sub(1, Relaxed);
         let gen = self.0.generation();
+        #[cfg(feature = "debug-refs")]
+        debug::freed(gen.address(), self.0.validity());
         gen.bump();
         if gen.try_lock_exclusive() {
-            std::mem::drop(unsafe { Box::from_raw(self.0.pointer().as_ptr()) });
+            unsafe { free_box(gen, self.0.pointer()) };
             unsafe { gen.unlock_exclusive() }
             LocalOrGlobalGeneration::free(gen);
         }
     }
 }
 
-impl<T> From<Sending<T>> for Strong<T> {
+impl<T: ?Sized> From<Sending<T>> for Strong<T> {
     fn from(it: Sending<T>) -> Self {
-        Strong(it.0.into())
+        let res = Strong(it.0.into());
+        mem::forget(it);
+        res
     }
 }
 
-impl<T> From<Box<T>> for Strong<T> {
+impl<T: ?Sized> From<Box<T>> for Strong<T> {
     fn from(it: Box<T>) -> Self {
+        stats::STRONGS.fetch_add(1, Relaxed);
+        let genptr = LocalGeneration::new();
+        #[cfg(feature = "debug-refs")]
+        debug::allocated(genptr.0.as_ptr() as usize, genptr.count());
         Self(
             LocalRaw {
-                genref: COUNTER_INIT,
-                genptr: LocalGeneration::new(),
+                genref: genptr.count(),
+                genptr,
                 boxptr: unsafe { NonNull::new_unchecked(Box::into_raw(it)) },
             }
             .into(),
@@ -127,35 +311,96 @@ impl<T> From<Box<T>> for Strong<T> {
     }
 }
 
+impl<T> From<Vec<T>> for Strong<[T]> {
+    fn from(it: Vec<T>) -> Self {
+        Self::from(it.into_boxed_slice())
+    }
+}
+
+impl From<String> for Strong<str> {
+    fn from(it: String) -> Self {
+        Self::from(it.into_boxed_str())
+    }
+}
+
+impl From<&str> for Strong<str> {
+    fn from(it: &str) -> Self {
+        Self::from(Box::<str>::from(it))
+    }
+}
+
 #[repr(transparent)]
-pub struct Sending<T: 'static>(GlobalRaw<T>);
-unsafe impl<T: 'static + Send + Sync> Send for Sending<T> {}
-impl<T: 'static> Drop for Sending<T> {
+pub struct Sending<T: ?Sized + 'static>(GlobalRaw<T>);
+unsafe impl<T: ?Sized + 'static + Send + Sync> Send for Sending<T> {}
+impl<T: ?Sized + 'static> Drop for Sending<T> {
     fn drop(&mut self) {
         let _ = unsafe { Strong::from_raw(self.0.into()) };
     }
 }
 
 #[repr(transparent)]
-pub struct Sharing<T: 'static>(GlobalRaw<T>);
-unsafe impl<T: 'static + Sync> Send for Sharing<T> {}
+pub struct Sharing<T: ?Sized + 'static>(GlobalRaw<T>);
+unsafe impl<T: ?Sized + 'static + Sync> Send for Sharing<T> {}
 
-pub enum Transferrable<T: 'static> {
+pub enum Transferrable<T: ?Sized + 'static> {
     Send(Sending<T>),
     Sync(Sharing<T>),
 }
 
 #[repr(transparent)]
-pub struct Weak<T: 'static>(RawRef<T>);
-impl<T: 'static> Copy for Weak<T> {}
-impl<T: 'static> Clone for Weak<T> {
+pub struct Weak<T: ?Sized + 'static>(RawRef<T>);
+impl<T: ?Sized + 'static> Copy for Weak<T> {}
+impl<T: ?Sized + 'static> Clone for Weak<T> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
-#[allow(dead_code)]
-impl<T: 'static + Sync> Weak<T> {
+impl<T: ?Sized + 'static> PartialEq for Weak<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+            && self.0.generation().identity() == other.0.generation().identity()
+            && self.0.validity() == other.0.validity()
+    }
+}
+
+impl<T: ?Sized + 'static> Eq for Weak<T> {}
+
+impl<T: ?Sized + 'static> Hash for Weak<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.pointer().cast::<()>().hash(state);
+        self.0.generation().identity().hash(state);
+        self.0.validity().hash(state);
+    }
+}
+
+impl<T: ?Sized + 'static + Sync> Weak<T> {
+    /// Promotes the referent and wraps this alias for another thread.
+    /// Ownership stays with the `Strong`, and the other thread sees
+    /// [`AccessError::Dangling`] once it is dropped.
+    ///
+    /// ```
+    /// use std::{sync::mpsc, thread};
+    ///
+    /// use aloxtalk::memory::{AccessError, Strong, Weak};
+    ///
+    /// let strong = Strong::new(7);
+    /// let sharing = strong.alias().share();
+    /// let (read, seen) = mpsc::channel();
+    /// let (freed, wait) = mpsc::channel();
+    ///
+    /// let worker = thread::spawn(move || {
+    ///     let alias = Weak::from(sharing);
+    ///     read.send(*alias.read_blocking().unwrap()).unwrap();
+    ///     wait.recv().unwrap();
+    ///     alias.read_blocking().err()
+    /// });
+    ///
+    /// assert_eq!(seen.recv().unwrap(), 7);
+    /// drop(strong);
+    /// freed.send(()).unwrap();
+    /// assert!(matches!(worker.join().unwrap(), Some(AccessError::Dangling { .. })));
+    /// ```
     pub fn share(self) -> Sharing<T> {
         if let RawRefEnum::Global(g) = self.make_sharable().0.into() {
             Sharing(g)
@@ -167,7 +412,16 @@ impl<T: 'static + Sync> Weak<T> {
     pub fn make_sharable(self) -> Self {
         Weak(
             match self.0.into() {
-                RawRefEnum::Local(l) => l.globalize(),
+                RawRefEnum::Local(l) => {
+                    self.0
+                        .generation()
+                        .reach(|| l.globalize())
+                        .unwrap_or(GlobalRaw {
+                            genptr: GlobalGeneration::dangling(),
+                            boxptr: l.boxptr,
+                            genref: l.genref,
+                        })
+                }
                 RawRefEnum::Global(g) => g,
             }
             .into(),
@@ -175,32 +429,175 @@ impl<T: 'static + Sync> Weak<T> {
     }
 }
 
-impl<T> From<Sharing<T>> for Weak<T> {
+/// Types that have no padding and for which every bit pattern is a valid
+/// value, so [`Weak::try_read_copy`] can copy them while a writer is active
+/// and throw the copy away afterwards.
+///
+/// # Safety
+///
+/// Implementors must be `Copy`, have no padding bytes and accept any bit
+/// pattern of their size.
+pub unsafe trait Plain: Copy + 'static {}
+
+macro_rules! plain {
+    ($($t:ty),*) => {
+        $(unsafe impl Plain for $t {})*
+    };
+}
+
+plain!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Plain, const N: usize> Plain for [T; N] {}
+
+const OPTIMISTIC_ATTEMPTS: usize = 4;
+
+impl<T: Plain> Weak<T> {
+    pub fn try_read_copy(&self) -> Result<T, AccessError> {
+        if let LocalOrGlobalGeneration::Global(gen) = self.0.generation() {
+            for _ in 0..OPTIMISTIC_ATTEMPTS {
+                if let Some(value) =
+                    unsafe { gen.read_optimistic(self.0.validity(), self.0.pointer()) }
+                {
+                    return Ok(value);
+                }
+                std::hint::spin_loop();
+            }
+        }
+        self.try_read().map(|guard| *guard)
+    }
+}
+
+impl<T: ?Sized> From<Sharing<T>> for Weak<T> {
     fn from(it: Sharing<T>) -> Self {
         Weak(it.0.into())
     }
 }
 
-#[allow(dead_code)]
-impl<T> Weak<T> {
-    fn try_read(&self) -> Option<Reading<T>> {
+impl<T: ?Sized> Weak<T> {
+    pub fn try_read(&self) -> Result<Reading<T>, AccessError> {
+        self.reach(|gen| {
+            if self.0.validity() != gen.count() {
+                return Err(self.dangling());
+            }
+            if gen.try_lock_shared() {
+                return Ok(Reading::new(self.0));
+            }
+            Err(gen.access_error())
+        })
+    }
+
+    pub fn try_write(&self) -> Result<Writing<T>, AccessError> {
+        self.reach(|gen| {
+            if self.0.validity() != gen.count() {
+                return Err(self.dangling());
+            }
+            if gen.try_lock_exclusive() {
+                return Ok(Writing::new(self.0));
+            }
+            Err(gen.access_error())
+        })
+    }
+
+    pub fn try_read_upgradable(&self) -> Result<Upgrading<T>, AccessError> {
+        self.reach(|gen| {
+            if self.0.validity() != gen.count() {
+                return Err(self.dangling());
+            }
+            if gen.try_lock_upgradable() {
+                return Ok(Upgrading::new(self.0));
+            }
+            Err(gen.access_error())
+        })
+    }
+
+    pub fn read_blocking(&self) -> Result<Reading<T>, AccessError> {
+        self.read_for(None)
+    }
+
+    pub fn write_blocking(&self) -> Result<Writing<T>, AccessError> {
+        self.write_for(None)
+    }
+
+    pub fn read_timeout(&self, timeout: Duration) -> Result<Reading<T>, AccessError> {
+        self.read_for(Some(timeout))
+    }
+
+    pub fn write_timeout(&self, timeout: Duration) -> Result<Writing<T>, AccessError> {
+        self.write_for(Some(timeout))
+    }
+
+    fn read_for(&self, timeout: Option<Duration>) -> Result<Reading<T>, AccessError> {
         let gen = self.0.generation();
-        if self.0.validity() == gen.count() {
-            if self.0.generation().try_lock_shared() {
-                return Some(Reading(self.0));
+        self.reach(|gen| {
+            if self.0.validity() != gen.count() {
+                return Err(self.dangling());
             }
-        }
-        None
+            gen.enqueue();
+            Ok(())
+        })?;
+        let res = if !gen.lock_shared_for(timeout) {
+            Err(gen.access_error())
+        } else if self.0.validity() != gen.count() {
+            unsafe { gen.unlock_shared() }
+            Err(self.dangling())
+        } else {
+            Ok(Reading::new(self.0))
+        };
+        gen.dequeue();
+        res
     }
 
-    fn try_write(&self) -> Option<Writing<T>> {
+    fn write_for(&self, timeout: Option<Duration>) -> Result<Writing<T>, AccessError> {
         let gen = self.0.generation();
-        if self.0.validity() == gen.count() {
-            if self.0.generation().try_lock_exclusive() {
-                return Some(Writing(self.0));
+        self.reach(|gen| {
+            if self.0.validity() != gen.count() {
+                return Err(self.dangling());
             }
+            gen.enqueue();
+            Ok(())
+        })?;
+        let res = if !gen.lock_exclusive_for(timeout) {
+            Err(gen.access_error())
+        } else if self.0.validity() != gen.count() {
+            unsafe { gen.unlock_exclusive() }
+            Err(self.dangling())
+        } else {
+            Ok(Writing::new(self.0))
+        };
+        gen.dequeue();
+        res
+    }
+
+    #[cfg(feature = "debug-refs")]
+    pub fn provenance(&self) -> Option<Provenance> {
+        debug::lookup(self.0.generation().address(), self.0.validity())
+    }
+
+    fn dangling(&self) -> AccessError {
+        AccessError::Dangling {
+            #[cfg(feature = "debug-refs")]
+            provenance: self.provenance(),
         }
-        None
+    }
+
+    pub fn is_alive(&self) -> bool {
+        let gen = self.0.generation();
+        gen.reach(|| self.0.validity() == gen.count())
+            .unwrap_or(false)
+    }
+
+    // Runs `f` on the counter unless its slab is gone, in which case the
+    // alias can only be dangling.
+    fn reach<R>(
+        &self,
+        f: impl FnOnce(LocalOrGlobalGeneration) -> Result<R, AccessError>,
+    ) -> Result<R, AccessError> {
+        let gen = self.0.generation();
+        gen.reach(|| f(gen)).unwrap_or_else(|| Err(self.dangling()))
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.0.pointer().cast::<()>() == other.0.pointer().cast()
     }
 
     pub(crate) fn as_raw(self) -> RawRef<T> {
@@ -214,9 +611,63 @@ impl<T> Weak<T> {
     }
 }
 
-pub struct Reading<T: 'static>(RawRef<T>);
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "debug-refs"), derive(Copy))]
+pub enum AccessError {
+    Dangling {
+        #[cfg(feature = "debug-refs")]
+        provenance: Option<Provenance>,
+    },
+    WriteLocked,
+    ReadLocked {
+        readers: Option<usize>,
+    },
+    Globalized,
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "debug-refs")]
+            AccessError::Dangling {
+                provenance: Some(provenance),
+            } => write!(f, "dangling reference, {provenance}"),
+            AccessError::Dangling { .. } => f.write_str("dangling reference"),
+            AccessError::WriteLocked => f.write_str("object is locked for writing"),
+            AccessError::ReadLocked { readers: Some(n) } => {
+                write!(f, "object is locked by {n} readers")
+            }
+            AccessError::ReadLocked { readers: None } => f.write_str("object is locked by readers"),
+            AccessError::Globalized => f.write_str("object is shared with another thread"),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+pub struct Reading<T: ?Sized + 'static>(RawRef<T>);
+
+impl<T: ?Sized + 'static> Reading<T> {
+    fn new(raw: RawRef<T>) -> Self {
+        stats::READINGS.fetch_add(1, Relaxed);
+        Self(raw)
+    }
+}
 
-impl<T: 'static> Deref for Reading<T> {
+impl<T: 'static> Reading<T> {
+    pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&T) -> &U) -> MappedReading<U> {
+        let value = NonNull::from(f(&this));
+        let raw = this.0.cast();
+        mem::forget(this);
+        MappedReading {
+            value,
+            raw,
+            release: |raw| mem::drop(Reading::<T>(raw.cast())),
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Deref for Reading<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -224,33 +675,102 @@ impl<T: 'static> Deref for Reading<T> {
     }
 }
 
-impl<T: 'static> Clone for Reading<T> {
+impl<T: ?Sized + 'static> Clone for Reading<T> {
     fn clone(&self) -> Self {
         if !self.0.generation().try_lock_shared() {
             panic!()
         }
-        Self(self.0)
+        Self::new(self.0)
     }
 }
 
-impl<T: 'static> Drop for Reading<T> {
+impl<T: ?Sized + 'static> Drop for Reading<T> {
     fn drop(&mut self) {
+        stats::READINGS.fetch_sub(1, Relaxed);
         let gen = self.0.generation();
-        if self.0.validity() != gen.count() {
-            if unsafe { gen.try_shared_into_exclusive() } {
-                std::mem::drop(unsafe { Box::from_raw(self.0.pointer().as_ptr()) });
-                unsafe { gen.unlock_exclusive() }
-                LocalOrGlobalGeneration::free(gen);
-                return;
-            }
+        if self.0.validity() != gen.count() && unsafe { gen.try_shared_into_exclusive() } {
+            unsafe { free_box(gen, self.0.pointer()) };
+            unsafe { gen.unlock_exclusive() }
+            LocalOrGlobalGeneration::free(gen);
+            return;
         }
         unsafe { gen.unlock_shared() }
     }
 }
 
-pub struct Writing<T: 'static>(RawRef<T>);
+pub struct Upgrading<T: ?Sized + 'static>(RawRef<T>);
+
+impl<T: ?Sized + 'static> Upgrading<T> {
+    fn new(raw: RawRef<T>) -> Self {
+        stats::READINGS.fetch_add(1, Relaxed);
+        Self(raw)
+    }
+
+    pub fn try_upgrade(self) -> Result<Writing<T>, Self> {
+        if unsafe { self.0.generation().try_upgrade() } {
+            let res = Writing::new(self.0);
+            stats::READINGS.fetch_sub(1, Relaxed);
+            mem::forget(self);
+            Ok(res)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Deref for Upgrading<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.0.pointer().as_ref() }
+    }
+}
+
+impl<T: ?Sized + 'static> Drop for Upgrading<T> {
+    fn drop(&mut self) {
+        stats::READINGS.fetch_sub(1, Relaxed);
+        let gen = self.0.generation();
+        if self.0.validity() != gen.count() && unsafe { gen.try_upgrade() } {
+            unsafe { free_box(gen, self.0.pointer()) };
+            unsafe { gen.unlock_exclusive() }
+            LocalOrGlobalGeneration::free(gen);
+            return;
+        }
+        unsafe { gen.unlock_upgradable() }
+    }
+}
+
+pub struct Writing<T: ?Sized + 'static>(RawRef<T>);
 
-impl<T: 'static> Deref for Writing<T> {
+impl<T: ?Sized + 'static> Writing<T> {
+    fn new(raw: RawRef<T>) -> Self {
+        stats::WRITINGS.fetch_add(1, Relaxed);
+        Self(raw)
+    }
+
+    pub fn downgrade(self) -> Reading<T> {
+        unsafe { self.0.generation().downgrade() }
+        let res = Reading::new(self.0);
+        stats::WRITINGS.fetch_sub(1, Relaxed);
+        mem::forget(self);
+        res
+    }
+}
+
+impl<T: 'static> Writing<T> {
+    pub fn map<U: ?Sized>(mut this: Self, f: impl FnOnce(&mut T) -> &mut U) -> MappedWriting<U> {
+        let value = NonNull::from(f(&mut this));
+        let raw = this.0.cast();
+        mem::forget(this);
+        MappedWriting {
+            value,
+            raw,
+            release: |raw| mem::drop(Writing::<T>(raw.cast())),
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Deref for Writing<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -258,17 +778,18 @@ impl<T: 'static> Deref for Writing<T> {
     }
 }
 
-impl<T: 'static> DerefMut for Writing<T> {
+impl<T: ?Sized + 'static> DerefMut for Writing<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         unsafe { self.0.pointer().as_mut() }
     }
 }
 
-impl<T: 'static> Drop for Writing<T> {
+impl<T: ?Sized + 'static> Drop for Writing<T> {
     fn drop(&mut self) {
+        stats::WRITINGS.fetch_sub(1, Relaxed);
         let gen = self.0.generation();
         if self.0.validity() != gen.count() {
-            std::mem::drop(unsafe { Box::from_raw(self.0.pointer().as_ptr()) });
+            unsafe { free_box(gen, self.0.pointer()) };
             unsafe { gen.unlock_exclusive() }
             LocalOrGlobalGeneration::free(gen);
         } else {
@@ -276,3 +797,75 @@ impl<T: 'static> Drop for Writing<T> {
         }
     }
 }
+
+pub struct MappedReading<U: ?Sized + 'static> {
+    value: NonNull<U>,
+    raw: RawRef<()>,
+    release: fn(RawRef<()>),
+}
+
+impl<U: ?Sized + 'static> MappedReading<U> {
+    pub fn map<V: ?Sized>(this: Self, f: impl FnOnce(&U) -> &V) -> MappedReading<V> {
+        let value = NonNull::from(f(&this));
+        let (raw, release) = (this.raw, this.release);
+        mem::forget(this);
+        MappedReading {
+            value,
+            raw,
+            release,
+        }
+    }
+}
+
+impl<U: ?Sized + 'static> Deref for MappedReading<U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<U: ?Sized + 'static> Drop for MappedReading<U> {
+    fn drop(&mut self) {
+        (self.release)(self.raw)
+    }
+}
+
+pub struct MappedWriting<U: ?Sized + 'static> {
+    value: NonNull<U>,
+    raw: RawRef<()>,
+    release: fn(RawRef<()>),
+}
+
+impl<U: ?Sized + 'static> MappedWriting<U> {
+    pub fn map<V: ?Sized>(mut this: Self, f: impl FnOnce(&mut U) -> &mut V) -> MappedWriting<V> {
+        let value = NonNull::from(f(&mut this));
+        let (raw, release) = (this.raw, this.release);
+        mem::forget(this);
+        MappedWriting {
+            value,
+            raw,
+            release,
+        }
+    }
+}
+
+impl<U: ?Sized + 'static> Deref for MappedWriting<U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<U: ?Sized + 'static> DerefMut for MappedWriting<U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<U: ?Sized + 'static> Drop for MappedWriting<U> {
+    fn drop(&mut self) {
+        (self.release)(self.raw)
+    }
+}
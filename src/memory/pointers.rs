@@ -6,8 +6,8 @@ use super::counter::*;
 
 macro_rules! clone_copy {
     ($t:ident) => {
-        impl<T: 'static> Copy for $t<T> {}
-        impl<T: 'static> Clone for $t<T> {
+        impl<T: ?Sized + 'static> Copy for $t<T> {}
+        impl<T: ?Sized + 'static> Clone for $t<T> {
             fn clone(&self) -> Self {
                 *self
             }
@@ -16,14 +16,14 @@ macro_rules! clone_copy {
 }
 
 #[repr(C)]
-pub(crate) struct LocalRaw<T: 'static> {
+pub(crate) struct LocalRaw<T: ?Sized + 'static> {
     pub(crate) genptr: LocalGeneration,
     pub(crate) boxptr: NonNull<T>,
-    pub(crate) genref: u32,
+    pub(crate) genref: Count,
 }
 clone_copy!(LocalRaw);
 
-impl<T: 'static> LocalRaw<T> {
+impl<T: ?Sized + 'static> LocalRaw<T> {
     pub(crate) fn globalize(&self) -> GlobalRaw<T> {
         let LocalRaw {
             genref,
@@ -40,23 +40,35 @@ impl<T: 'static> LocalRaw<T> {
 }
 
 #[repr(C)]
-pub(crate) struct GlobalRaw<T: 'static> {
+pub(crate) struct GlobalRaw<T: ?Sized + 'static> {
     pub(crate) genptr: GlobalGeneration,
     pub(crate) boxptr: NonNull<T>,
-    pub(crate) genref: u32,
+    pub(crate) genref: Count,
 }
 clone_copy!(GlobalRaw);
 
 #[repr(C)]
-pub(crate) struct RawRef<T: 'static> {
+pub(crate) struct RawRef<T: ?Sized + 'static> {
     pub(crate) genptr: GenerationUnion,
     pub(crate) boxptr: NonNull<T>,
-    pub(crate) genref: u32,
+    pub(crate) genref: Count,
     pub(crate) discriminant: LocalOrGlobal,
     pub(crate) ownership: OwnershipBit,
 }
 clone_copy!(RawRef);
 
+impl<T: ?Sized + 'static> RawRef<T> {
+    pub(crate) fn cast<U: 'static>(self) -> RawRef<U> {
+        RawRef {
+            genptr: self.genptr,
+            boxptr: self.boxptr.cast(),
+            genref: self.genref,
+            discriminant: self.discriminant,
+            ownership: self.ownership,
+        }
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub(crate) enum LocalOrGlobal {
@@ -93,7 +105,7 @@ impl From<GlobalGeneration> for GenerationUnion {
     }
 }
 
-impl<T: 'static> From<RawRefEnum<T>> for RawRef<T> {
+impl<T: ?Sized + 'static> From<RawRefEnum<T>> for RawRef<T> {
     fn from(it: RawRefEnum<T>) -> Self {
         match it {
             RawRefEnum::Local(LocalRaw {
@@ -122,12 +134,12 @@ impl<T: 'static> From<RawRefEnum<T>> for RawRef<T> {
     }
 }
 
-pub(crate) enum RawRefEnum<T: 'static> {
+pub(crate) enum RawRefEnum<T: ?Sized + 'static> {
     Local(LocalRaw<T>),
     Global(GlobalRaw<T>),
 }
 
-impl<T: 'static> From<RawRef<T>> for RawRefEnum<T> {
+impl<T: ?Sized + 'static> From<RawRef<T>> for RawRefEnum<T> {
     fn from(it: RawRef<T>) -> Self {
         let RawRef {
             genptr,
@@ -152,25 +164,25 @@ impl<T: 'static> From<RawRef<T>> for RawRefEnum<T> {
     }
 }
 
-impl<T: 'static> From<LocalRaw<T>> for RawRef<T> {
+impl<T: ?Sized + 'static> From<LocalRaw<T>> for RawRef<T> {
     fn from(it: LocalRaw<T>) -> Self {
         RawRefEnum::Local(it).into()
     }
 }
-impl<T: 'static> From<GlobalRaw<T>> for RawRef<T> {
+impl<T: ?Sized + 'static> From<GlobalRaw<T>> for RawRef<T> {
     fn from(it: GlobalRaw<T>) -> Self {
         RawRefEnum::Global(it).into()
     }
 }
 
-pub(crate) trait Reference<T: 'static> {
+pub(crate) trait Reference<T: ?Sized + 'static> {
     type Gen: Generation + GenerationCounter + AccessControl;
     fn pointer(&self) -> NonNull<T>;
-    fn validity(&self) -> u32;
+    fn validity(&self) -> Count;
     fn generation(&self) -> Self::Gen;
 }
 
-impl<T: 'static> Reference<T> for LocalRaw<T> {
+impl<T: ?Sized + 'static> Reference<T> for LocalRaw<T> {
     type Gen = LocalGeneration;
 
     #[inline(always)]
@@ -178,7 +190,7 @@ impl<T: 'static> Reference<T> for LocalRaw<T> {
         self.boxptr
     }
     #[inline(always)]
-    fn validity(&self) -> u32 {
+    fn validity(&self) -> Count {
         self.genref
     }
     #[inline(always)]
@@ -187,14 +199,14 @@ impl<T: 'static> Reference<T> for LocalRaw<T> {
     }
 }
 
-impl<T: 'static> Reference<T> for GlobalRaw<T> {
+impl<T: ?Sized + 'static> Reference<T> for GlobalRaw<T> {
     type Gen = GlobalGeneration;
     #[inline(always)]
     fn pointer(&self) -> NonNull<T> {
         self.boxptr
     }
     #[inline(always)]
-    fn validity(&self) -> u32 {
+    fn validity(&self) -> Count {
         self.genref
     }
     #[inline(always)]
@@ -203,7 +215,7 @@ impl<T: 'static> Reference<T> for GlobalRaw<T> {
     }
 }
 
-impl<T: 'static> Reference<T> for RawRef<T> {
+impl<T: ?Sized + 'static> Reference<T> for RawRef<T> {
     type Gen = LocalOrGlobalGeneration;
 
     #[inline(always)]
@@ -215,7 +227,7 @@ impl<T: 'static> Reference<T> for RawRef<T> {
     }
 
     #[inline(always)]
-    fn validity(&self) -> u32 {
+    fn validity(&self) -> Count {
         match (*self).into() {
             RawRefEnum::Local(l) => l.validity(),
             RawRefEnum::Global(g) => g.validity(),
@@ -232,6 +244,7 @@ impl<T: 'static> Reference<T> for RawRef<T> {
 }
 
 #[test]
+#[cfg(not(feature = "wide-generations"))]
 fn size_concerns() {
     assert_eq!(
         mem::size_of::<LocalRaw<String>>(),
@@ -247,4 +260,53 @@ fn size_concerns() {
         mem::size_of::<RawRef<String>>(),
         mem::size_of::<(usize, usize, u32, u8, [u8; 3])>()
     );
+
+    assert_eq!(
+        mem::size_of::<LocalRaw<str>>(),
+        mem::size_of::<(usize, [usize; 2], u32)>()
+    );
+
+    assert_eq!(
+        mem::size_of::<RawRef<[String]>>(),
+        mem::size_of::<(usize, [usize; 2], u32, u8, [u8; 3])>()
+    );
+
+    assert_eq!(
+        mem::size_of::<RawRef<dyn std::fmt::Debug>>(),
+        mem::size_of::<(usize, [usize; 2], u32, u8, [u8; 3])>()
+    );
+}
+
+#[test]
+#[cfg(feature = "wide-generations")]
+fn size_concerns() {
+    assert_eq!(
+        mem::size_of::<LocalRaw<String>>(),
+        mem::size_of::<(usize, usize, u64)>()
+    );
+
+    assert_eq!(
+        mem::size_of::<GlobalRaw<String>>(),
+        mem::size_of::<(usize, usize, u64)>()
+    );
+
+    assert_eq!(
+        mem::size_of::<RawRef<String>>(),
+        mem::size_of::<(usize, usize, u64, u8, [u8; 7])>()
+    );
+
+    assert_eq!(
+        mem::size_of::<LocalRaw<str>>(),
+        mem::size_of::<(usize, [usize; 2], u64)>()
+    );
+
+    assert_eq!(
+        mem::size_of::<RawRef<[String]>>(),
+        mem::size_of::<(usize, [usize; 2], u64, u8, [u8; 7])>()
+    );
+
+    assert_eq!(
+        mem::size_of::<RawRef<dyn std::fmt::Debug>>(),
+        mem::size_of::<(usize, [usize; 2], u64, u8, [u8; 7])>()
+    );
 }
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use super::counter::{GlobalGeneration, LocalGeneration};
+
+pub(crate) static STRONGS: AtomicUsize = AtomicUsize::new(0);
+pub(crate) static READINGS: AtomicUsize = AtomicUsize::new(0);
+pub(crate) static WRITINGS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CounterStats {
+    pub allocated: usize,
+    pub free: usize,
+    pub retired: usize,
+    // local: counters redirected to a global one, global: promotions so far
+    pub globalized: usize,
+    pub slab_bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub local: CounterStats,
+    pub global: CounterStats,
+    pub parked: usize,
+    pub strongs: usize,
+    pub readings: usize,
+    pub writings: usize,
+}
+
+pub fn stats() -> MemoryStats {
+    MemoryStats {
+        local: LocalGeneration::stats(),
+        global: GlobalGeneration::stats(),
+        parked: LocalGeneration::parked(),
+        strongs: STRONGS.load(Relaxed),
+        readings: READINGS.load(Relaxed),
+        writings: WRITINGS.load(Relaxed),
+    }
+}
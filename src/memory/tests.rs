@@ -2,7 +2,13 @@
 use std::mem;
 
 #[cfg(test)]
-use std::{assert_matches::assert_matches, thread};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+#[cfg(test)]
+use std::thread;
+
+#[cfg(test)]
+use std::time::Duration;
 
 #[cfg(test)]
 use parking_lot::Mutex;
@@ -11,7 +17,24 @@ use parking_lot::Mutex;
 use crate::memory::counter::*;
 
 #[cfg(test)]
-use super::Strong;
+use crate::memory::pointers::RawRef;
+
+#[cfg(test)]
+use crate::memory::weak_map::WeakKeyMap;
+
+#[cfg(test)]
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+
+#[cfg(test)]
+use super::{bounded, channel, select, select_timeout, Received};
+
+#[cfg(test)]
+use super::{stats, AccessError, MappedReading, MappedWriting, Reading, Strong, Weak, Writing};
+
+#[cfg(test)]
+fn dangling(err: Option<AccessError>) -> bool {
+    matches!(err, Some(AccessError::Dangling { .. }))
+}
 
 #[test]
 fn local_allocation_single() {
@@ -64,7 +87,7 @@ fn global_allocation_single() {
     let _ = a.join();
     let _ = b.join();
 
-    assert_matches!(GlobalGeneration::free_list_size(), 1 | 2);
+    assert!(matches!(GlobalGeneration::free_list_size(), 1 | 2));
 
     GlobalGeneration::leak_all_and_reset();
 }
@@ -99,7 +122,7 @@ fn global_allocation_multi() {
     let _ = a.join();
     let _ = b.join();
 
-    assert_matches!(GlobalGeneration::free_list_size(), 100..=200);
+    assert!(matches!(GlobalGeneration::free_list_size(), 100..=200));
 
     GlobalGeneration::leak_all_and_reset();
 }
@@ -259,7 +282,7 @@ fn strong_reading() {
 
     assert_eq!(*p, *q);
 
-    assert!(s.try_write().is_none());
+    assert!(s.try_write().is_err());
 }
 
 #[test]
@@ -270,7 +293,7 @@ fn strong_writing() {
 
     assert_eq!(*p, 1);
 
-    assert!(s.try_read().is_none());
+    assert!(s.try_read().is_err());
 
     *p = 2;
 
@@ -292,7 +315,7 @@ fn weak_reading() {
 
     assert_eq!(*p, *q);
 
-    assert!(s.try_write().is_none());
+    assert!(s.try_write().is_err());
 }
 
 #[test]
@@ -304,7 +327,7 @@ fn weak_writing() {
 
     assert_eq!(*p, 1);
 
-    assert!(s.try_read().is_none());
+    assert!(s.try_read().is_err());
 
     *p = 2;
 
@@ -343,21 +366,931 @@ fn exclusive_access() {
 
     {
         let _p = s.try_write().unwrap();
-        assert!(w.try_read().is_none());
+        assert!(w.try_read().is_err());
     }
 
     {
         let _p = w.try_write().unwrap();
-        assert!(s.try_read().is_none());
+        assert!(s.try_read().is_err());
     }
 
     {
         let _p = s.try_read().unwrap();
-        assert!(w.try_write().is_none());
+        assert!(w.try_write().is_err());
     }
 
     {
         let _p = w.try_read().unwrap();
-        assert!(s.try_write().is_none());
+        assert!(s.try_write().is_err());
+    }
+}
+
+#[test]
+fn recycled_generation_alias() {
+    mem::drop(Strong::new(1u32));
+
+    let s = Strong::new(2u32);
+    let w = s.alias();
+
+    assert_eq!(*w.try_read().unwrap(), 2);
+
+    mem::drop(s);
+
+    assert!(w.try_read().is_err());
+}
+
+#[test]
+fn access_errors() {
+    let s = Strong::new(1u32);
+    let w = s.alias();
+
+    {
+        let _p = s.try_write().unwrap();
+        assert_eq!(w.try_read().err(), Some(AccessError::WriteLocked));
+        assert_eq!(s.try_write().err(), Some(AccessError::WriteLocked));
+    }
+
+    {
+        let _p = s.try_read().unwrap();
+        let _q = w.try_read().unwrap();
+        assert_eq!(
+            w.try_write().err(),
+            Some(AccessError::ReadLocked { readers: Some(2) })
+        );
+    }
+
+    mem::drop(s);
+
+    assert!(dangling(w.try_read().err()));
+    assert!(dangling(w.try_write().err()));
+}
+
+#[test]
+fn globalized_access_error() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let s = Strong::new(1u32);
+    let w = s.alias();
+    let shared = w.make_sharable();
+
+    {
+        let _p = shared.try_write().unwrap();
+        assert_eq!(w.try_read().err(), Some(AccessError::Globalized));
+        assert_eq!(shared.try_read().err(), Some(AccessError::WriteLocked));
+    }
+
+    {
+        let _p = shared.try_read().unwrap();
+        assert_eq!(
+            shared.try_write().err(),
+            Some(AccessError::ReadLocked { readers: None })
+        );
+    }
+
+    mem::drop(s);
+
+    assert!(dangling(shared.try_read().err()));
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn local_refs_do_not_block() {
+    let s = Strong::new(1u32);
+    let w = s.alias();
+
+    let _p = s.try_write().unwrap();
+
+    assert_eq!(w.read_blocking().err(), Some(AccessError::WriteLocked));
+    assert_eq!(
+        w.write_timeout(Duration::from_secs(60)).err(),
+        Some(AccessError::WriteLocked)
+    );
+}
+
+#[test]
+fn global_refs_block() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let s = Strong::new(1u32).make_sharable();
+    let shared = s.alias().share();
+
+    let p = s.write_blocking().unwrap();
+
+    let worker = thread::spawn(move || {
+        let w = Weak::from(shared);
+        let mut q = w.write_blocking().unwrap();
+        *q += 1;
+    });
+
+    thread::sleep(Duration::from_millis(20));
+    mem::drop(p);
+    let _ = worker.join();
+
+    assert_eq!(*s.read_blocking().unwrap(), 2);
+
+    mem::drop(s);
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn global_refs_time_out() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let s = Strong::new(1u32).make_sharable();
+
+    {
+        let _p = s.read_timeout(Duration::from_millis(10)).unwrap();
+        assert!(s.read_timeout(Duration::from_millis(10)).is_ok());
+        assert_eq!(
+            s.write_timeout(Duration::from_millis(10)).err(),
+            Some(AccessError::ReadLocked { readers: None })
+        );
+    }
+
+    {
+        let _p = s.write_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(
+            s.read_timeout(Duration::from_millis(10)).err(),
+            Some(AccessError::WriteLocked)
+        );
     }
+
+    let w = s.alias();
+    mem::drop(s);
+
+    assert!(dangling(w.read_blocking().err()));
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn blocked_waiters_outlive_owner() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+    struct Probe(std::sync::Arc<AtomicUsize>);
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    let s = Strong::new(Probe(dropped.clone())).make_sharable();
+    let (r, w) = (s.alias().share(), s.alias().share());
+    let p = s.write_blocking().unwrap();
+
+    let reader = thread::spawn(move || Weak::from(r).read_blocking().err());
+    let writer = thread::spawn(move || Weak::from(w).write_timeout(Duration::from_secs(60)).err());
+    thread::sleep(Duration::from_millis(20));
+    mem::drop(s);
+    mem::drop(p);
+
+    assert!(dangling(reader.join().unwrap()));
+    assert!(dangling(writer.join().unwrap()));
+    assert_eq!(dropped.load(Relaxed), 1);
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn upgradable_reading() {
+    let s = Strong::new(1u32);
+    let w = s.alias();
+
+    let u = w.try_read_upgradable().unwrap();
+    let p = s.try_read().unwrap();
+
+    assert_eq!(*u, *p);
+    assert_eq!(
+        s.try_read_upgradable().err(),
+        Some(AccessError::ReadLocked { readers: Some(2) })
+    );
+    assert!(s.try_write().is_err());
+
+    let u = u.try_upgrade().err().unwrap();
+    mem::drop(p);
+
+    let mut q = u.try_upgrade().ok().unwrap();
+    assert!(w.try_read().is_err());
+    *q = 2;
+
+    let r = q.downgrade();
+    assert_eq!(*r, 2);
+    assert_eq!(*w.try_read().unwrap(), 2);
+    assert!(w.try_write().is_err());
+
+    mem::drop(r);
+    assert!(w.try_write().is_ok());
+}
+
+#[test]
+fn upgradable_outlives_strong() {
+    let s = Strong::new(1u32);
+    let w = s.alias();
+
+    let u = w.try_read_upgradable().unwrap();
+    mem::drop(s);
+
+    assert_eq!(*u, 1);
+    assert!(dangling(w.try_read_upgradable().err()));
+
+    mem::drop(u);
+    assert!(dangling(w.try_read().err()));
+}
+
+#[test]
+fn global_upgradable_reading() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let s = Strong::new(1u32).make_sharable();
+
+    let u = s.try_read_upgradable().unwrap();
+    let p = s.try_read().unwrap();
+    assert!(s.try_read_upgradable().is_err());
+
+    let u = u.try_upgrade().err().unwrap();
+    mem::drop(p);
+
+    let mut q = u.try_upgrade().ok().unwrap();
+    *q = 2;
+    let r = q.downgrade();
+    assert_eq!(*s.try_read().unwrap(), 2);
+    assert!(s.try_write().is_err());
+    mem::drop(r);
+
+    mem::drop(s);
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn mapped_guards() {
+    let s = Strong::new((1u32, vec![2u32, 3]));
+    let w = s.alias();
+
+    let first = Reading::map(s.try_read().unwrap(), |t| &t.0);
+    let second = Reading::map(w.try_read().unwrap(), |t| &t.1[..]);
+    let last = MappedReading::map(second, |v| &v[1]);
+
+    assert_eq!((*first, *last), (1, 3));
+    assert!(w.try_write().is_err());
+
+    mem::drop((first, last));
+
+    let mut items = Writing::map(w.try_write().unwrap(), |t| &mut t.1);
+    assert!(s.try_read().is_err());
+    items.push(4);
+
+    let mut last = MappedWriting::map(items, |v| v.last_mut().unwrap());
+    *last += 1;
+    mem::drop(last);
+
+    assert_eq!(s.try_read().unwrap().1, [2, 3, 5]);
+}
+
+#[test]
+fn mapped_guard_outlives_strong() {
+    let s = Strong::new((1u32, String::from("kept")));
+    let w = s.alias();
+
+    let name = Reading::map(w.try_read().unwrap(), |t| t.1.as_str());
+    mem::drop(s);
+
+    assert_eq!(&*name, "kept");
+    assert!(dangling(w.try_read().err()));
+
+    mem::drop(name);
+    assert!(dangling(w.try_read().err()));
+}
+
+#[cfg(test)]
+struct Escaped(RawRef<u32>);
+
+#[cfg(test)]
+unsafe impl Send for Escaped {}
+
+#[test]
+fn counters_survive_thread_exit() {
+    let parked = LocalGeneration::parked();
+
+    let (live, dead) = thread::spawn(|| {
+        let live = Strong::new(1u32);
+        let dead = Strong::new(2u32);
+        let escaped = (Escaped(live.into_raw()), Escaped(dead.alias().as_raw()));
+        mem::drop(dead);
+        escaped
+    })
+    .join()
+    .unwrap();
+
+    assert!(LocalGeneration::parked() >= parked + 32);
+
+    let live = unsafe { Strong::from_raw(live.0) };
+    let dead = unsafe { Weak::from_raw(dead.0) };
+
+    assert_eq!(*live.try_read().unwrap(), 1);
+    assert!(dangling(dead.try_read().err()));
+
+    mem::drop(live);
+}
+
+#[test]
+fn short_lived_threads() {
+    for _ in 0..64 {
+        thread::spawn(|| {
+            let v = (0..100).map(Strong::new).collect::<Vec<_>>();
+            let w = v.iter().map(Strong::alias).collect::<Vec<_>>();
+            mem::drop(v);
+            assert!(w.iter().all(|w| w.try_read().is_err()));
+        })
+        .join()
+        .unwrap();
+    }
+}
+
+#[test]
+fn reclaim_local_slabs() {
+    let _lock = GLOBAL_TEST.lock();
+
+    thread::spawn(|| {
+        let v = (0..100).map(Strong::new).collect::<Vec<_>>();
+        let allocated = LocalGeneration::allocations();
+        assert!(allocated > 100);
+
+        let keep = v.into_iter().last().unwrap();
+        let released = reclaim();
+        let local = allocated - LocalGeneration::allocations();
+
+        assert!(local >= 32);
+        assert!(released >= local);
+        assert_eq!(*keep.try_read().unwrap(), 99);
+
+        let w = (0..100).map(Strong::new).collect::<Vec<_>>();
+        assert_eq!(*w[42].try_read().unwrap(), 42);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn reclaim_parked_slabs() {
+    let _lock = GLOBAL_TEST.lock();
+
+    thread::spawn(|| {
+        let v = (0..20).map(Strong::new).collect::<Vec<_>>();
+        mem::drop(v);
+    })
+    .join()
+    .unwrap();
+
+    let parked = LocalGeneration::parked();
+    let released = reclaim();
+
+    assert!(released >= 32);
+    assert!(LocalGeneration::parked() <= parked - 32);
+}
+
+#[test]
+fn reclaim_global_slabs() {
+    let _lock = GLOBAL_TEST.lock();
+    GlobalGeneration::leak_all_and_reset();
+
+    let v = (0..40).map(|_| GlobalGeneration::new()).collect::<Vec<_>>();
+    let allocated = GlobalGeneration::allocations();
+    for g in v {
+        GlobalGeneration::free(g);
+    }
+
+    let released = reclaim();
+
+    assert!(released >= 32);
+    assert_eq!(GlobalGeneration::allocations(), allocated - 32);
+    assert_eq!(GlobalGeneration::free_list_size(), 8);
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn reclaim_aliased_slabs() {
+    let _lock = GLOBAL_TEST.lock();
+
+    thread::spawn(|| {
+        let v = (0..100).map(Strong::new).collect::<Vec<_>>();
+        let stale = v[3].alias();
+        let allocated = LocalGeneration::allocations();
+        mem::drop(v);
+
+        assert!(reclaim() >= 80);
+        assert_eq!(LocalGeneration::allocations(), allocated - 80);
+        assert!(dangling(stale.try_read().err()));
+        assert!(!stale.is_alive());
+
+        let w = (0..200).map(Strong::new).collect::<Vec<_>>();
+        assert!(dangling(stale.try_read().err()));
+        assert!(!stale.make_sharable().is_alive());
+        assert_eq!(*w[3].try_read().unwrap(), 3);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn reclaim_aliased_global_slabs() {
+    let _lock = GLOBAL_TEST.lock();
+    GlobalGeneration::leak_all_and_reset();
+
+    thread::spawn(|| {
+        let v = (0..40)
+            .map(|i| Strong::new(i).make_sharable())
+            .collect::<Vec<_>>();
+        let stale = v[3].alias();
+        let allocated = GlobalGeneration::allocations();
+        mem::drop(v);
+
+        assert!(reclaim() >= 64);
+        assert_eq!(GlobalGeneration::allocations(), allocated - 32);
+        assert!(dangling(stale.try_read().err()));
+        assert!(dangling(stale.read_blocking().err()));
+        assert!(!stale.is_alive());
+
+        let w = (0..80)
+            .map(|i| Strong::new(i).make_sharable())
+            .collect::<Vec<_>>();
+        assert!(dangling(stale.try_read().err()));
+        assert_eq!(*w[42].try_read().unwrap(), 42);
+    })
+    .join()
+    .unwrap();
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn globalizing_reuses_free_counters() {
+    let _lock = GLOBAL_TEST.lock();
+    GlobalGeneration::leak_all_and_reset();
+
+    thread::spawn(|| {
+        GlobalGeneration::free(GlobalGeneration::new());
+        let allocated = GlobalGeneration::allocations();
+        for _ in 0..4 {
+            mem::drop(Strong::new(0));
+        }
+
+        let strong = Strong::new(1).make_sharable();
+        assert_eq!(GlobalGeneration::free_list_size(), 0);
+        assert_eq!(GlobalGeneration::allocations(), allocated);
+
+        let weak = strong.alias();
+        mem::drop(strong);
+        assert_eq!(GlobalGeneration::free_list_size(), 1);
+        assert!(dangling(weak.try_read().err()));
+    })
+    .join()
+    .unwrap();
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn reclaim_parked_slabs_after_teardown() {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static KEPT : RefCell<Vec<Strong<u32>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    let _lock = GLOBAL_TEST.lock();
+
+    thread::spawn(|| {
+        let v = (0..20).map(Strong::new).collect::<Vec<_>>();
+        KEPT.with(|kept| kept.borrow_mut().push(Strong::new(20)));
+        mem::drop(v);
+    })
+    .join()
+    .unwrap();
+
+    let parked = LocalGeneration::parked();
+    reclaim();
+    assert!(LocalGeneration::parked() <= parked - 32);
+}
+
+#[test]
+fn memory_stats() {
+    thread::spawn(|| {
+        let v = (0..40).map(Strong::new).collect::<Vec<_>>();
+        let (p, q) = (v[0].try_read().unwrap(), v[1].try_read().unwrap());
+        let r = v[2].try_write().unwrap();
+
+        let during = stats();
+        assert_eq!(during.local.allocated, 32 + 48);
+        assert_eq!(during.local.slab_bytes, 80 * mem::size_of::<LocalCounter>());
+        assert!(during.strongs >= 40);
+        assert!(during.readings >= 2 && during.writings >= 1);
+
+        mem::drop((p, q, r));
+        let w = v[3].alias();
+        mem::drop(v);
+
+        let after = stats();
+        assert_eq!(after.local.free, 40);
+        assert_eq!(after.local.retired, 0);
+        assert!(w.try_read().is_err());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg(feature = "debug-refs")]
+fn dangling_provenance() {
+    let s = Strong::new(1u32);
+    let w = s.alias();
+
+    let live = w.provenance().unwrap();
+    assert!(live.freed.is_none());
+
+    mem::drop(s);
+
+    let Err(AccessError::Dangling {
+        provenance: Some(dead),
+    }) = w.try_read()
+    else {
+        panic!("dangling reads carry their provenance");
+    };
+    assert_eq!(dead.generation, live.generation);
+    assert!(dead.freed.is_some());
+
+    let report = dead.to_string();
+    assert!(report.contains("allocated at:"));
+    assert!(report.contains("freed at:"));
+
+    let t = Strong::new(2u32);
+    let (taken, u) = (t.alias(), t.try_take().ok().unwrap());
+    assert_eq!(*u, 2);
+    assert!(taken.provenance().unwrap().freed.is_some());
+
+    let next = Strong::new(3u32);
+    assert!(taken.provenance().is_none());
+    assert!(next.alias().provenance().is_some());
+}
+
+#[test]
+#[cfg(feature = "debug-refs")]
+fn shared_provenance() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let s = Strong::new(1u32);
+    let local = s.alias();
+    let s = s.make_sharable();
+    let global = s.alias();
+    assert!(local.provenance().is_some());
+    assert_eq!(global.provenance(), local.provenance());
+
+    let shared = global.share();
+    let report = thread::spawn(move || Weak::from(shared).provenance());
+    assert_eq!(report.join().unwrap(), local.provenance());
+
+    mem::drop(s);
+    for w in [local, global] {
+        match w.try_read() {
+            Err(AccessError::Dangling {
+                provenance: Some(p),
+            }) => assert!(p.freed.is_some()),
+            _ => panic!("expected a dangling report"),
+        }
+    }
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn optimistic_reads() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let local = Strong::new(7u64);
+    assert_eq!(local.try_read_copy(), Ok(7));
+
+    let s = Strong::new(1u64).make_sharable();
+    let w = s.alias();
+    assert_eq!(w.try_read_copy(), Ok(1));
+
+    {
+        let mut p = s.try_write().unwrap();
+        *p = 2;
+        assert_eq!(w.try_read_copy(), Err(AccessError::WriteLocked));
+        let p = Writing::downgrade(p);
+        assert_eq!(w.try_read_copy(), Ok(2));
+        mem::drop(p);
+    }
+
+    {
+        let p = s.try_read_upgradable().unwrap();
+        let mut q = p.try_upgrade().ok().unwrap();
+        *q = 3;
+        assert_eq!(w.try_read_copy(), Err(AccessError::WriteLocked));
+    }
+    assert_eq!(w.try_read_copy(), Ok(3));
+
+    mem::drop(s);
+    assert!(dangling(w.try_read_copy().err()));
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn optimistic_reads_are_not_torn() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let s = Strong::new([0u64; 2]).make_sharable();
+    let readers = (0..4)
+        .map(|_| {
+            let shared = s.alias().share();
+            thread::spawn(move || {
+                let w = Weak::from(shared);
+                let mut seen = 0;
+                while seen < 1000 {
+                    match w.try_read_copy() {
+                        Ok([a, b]) => {
+                            assert_eq!(a, b);
+                            seen = seen.max(a);
+                        }
+                        Err(AccessError::WriteLocked) => {}
+                        Err(err) => panic!("{err}"),
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for i in 1..=1000 {
+        let mut p = s.write_blocking().unwrap();
+        *p = [i, i];
+    }
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    mem::drop(s);
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn optimistic_reads_race_the_free() {
+    let _lock = GLOBAL_TEST.lock();
+
+    for _ in 0..100 {
+        let s = Strong::new([7u64; 8]).make_sharable();
+        let readers = (0..4)
+            .map(|_| {
+                let shared = s.alias().share();
+                thread::spawn(move || {
+                    let w = Weak::from(shared);
+                    loop {
+                        match w.try_read_copy() {
+                            Ok(value) => assert_eq!(value, [7; 8]),
+                            Err(AccessError::WriteLocked) => {}
+                            Err(err) => break err,
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        thread::yield_now();
+        mem::drop(s);
+        for reader in readers {
+            assert!(dangling(Some(reader.join().unwrap())));
+        }
+    }
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn weak_identity() {
+    use std::collections::HashSet;
+
+    let s = Strong::new(1u32);
+    let t = Strong::new(1u32);
+    assert!(s.alias() == s.alias());
+    assert!(s.alias() != t.alias());
+
+    let set = HashSet::from([s.alias(), s.alias(), t.alias()]);
+    assert_eq!(set.len(), 2);
+
+    let w = s.alias();
+    mem::drop(s);
+    let u = Strong::new(1u32);
+    assert!(!w.is_alive());
+    assert!(u.alias().is_alive());
+    assert!(w != u.alias());
+    assert!(set.contains(&w));
+}
+
+#[test]
+fn weak_identity_of_empty_values() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let (s, t) = (Strong::new(()), Strong::new(()));
+    assert!(s.alias() != t.alias());
+
+    let mut map = WeakKeyMap::new();
+    map.insert(s.alias(), 1);
+    map.insert(t.alias(), 2);
+    assert_eq!(map.len(), 2);
+
+    let x = Strong::<str>::from(Box::from(""));
+    let y = Strong::<str>::from(Box::from(""));
+    let mut map = WeakKeyMap::new();
+    map.insert(x.alias(), 1);
+    map.insert(y.alias(), 2);
+    assert_eq!(map.len(), 2);
+
+    let before = s.alias();
+    let s = s.make_sharable();
+    assert!(before == s.alias());
+    assert_eq!(map.get(&x.alias()), Some(&1));
+
+    mem::drop((s, t));
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn weak_key_map() {
+    let (s, t) = (Strong::new(1u32), Strong::new(2u32));
+    let (a, b) = (s.alias(), t.alias());
+
+    let mut map = WeakKeyMap::new();
+    assert_eq!(map.insert(a, "a"), None);
+    assert_eq!(map.insert(b, "b"), None);
+    assert_eq!(map.insert(a, "A"), Some("a"));
+    assert_eq!(map.get(&a), Some(&"A"));
+    assert_eq!(map.len(), 2);
+
+    mem::drop(s);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.iter().count(), 1);
+    assert_eq!(map.get(&a), None);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.insert(a, "dead"), None);
+    assert!(!map.contains_key(&a));
+
+    mem::drop(t);
+    assert_eq!(map.sweep(), 1);
+    assert!(map.is_empty());
+
+    let live = (0..10).map(Strong::new).collect::<Vec<_>>();
+    for s in &live {
+        map.insert(s.alias(), "live");
+    }
+    let dead = (0..20).map(Strong::new).collect::<Vec<_>>();
+    for s in &dead {
+        map.insert(s.alias(), "dead");
+    }
+    mem::drop(dead);
+    for s in (0..10).map(Strong::new).collect::<Vec<_>>() {
+        map.insert(s.alias(), "dead");
+    }
+    assert!(map.len() < 40);
+    map.sweep();
+    assert_eq!(map.len(), 10);
+    assert!(map.iter().all(|(_, &v)| v == "live"));
+}
+
+#[test]
+fn unsized_payloads() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let slice = Strong::from(vec![1, 2, 3]);
+    let w = slice.alias();
+    slice.try_write().unwrap()[1] = 5;
+    assert_eq!(&*w.try_read().unwrap(), &[1, 5, 3]);
+    assert_eq!(slice.try_take().ok().unwrap().len(), 3);
+    assert!(dangling(w.try_read().err()));
+
+    let string = Strong::<str>::from("abc");
+    string.try_write().unwrap().make_ascii_uppercase();
+    assert_eq!(&*string.try_read().unwrap(), "ABC");
+    assert!(string.alias() != Strong::<str>::from("ABC").alias());
+
+    let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+    struct Probe(std::sync::Arc<AtomicUsize>);
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+    let probe = Strong::from(Box::new(Probe(dropped.clone())) as Box<dyn Send + Sync>);
+    let shared = probe.alias().share();
+    thread::spawn(move || assert!(Weak::from(shared).read_blocking().is_ok()))
+        .join()
+        .unwrap();
+    assert_eq!(dropped.load(Relaxed), 0);
+    mem::drop(probe);
+    assert_eq!(dropped.load(Relaxed), 1);
+
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn channel_moves_and_shares() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let (tx, rx) = channel::<u32>();
+    let kept = Strong::new(1);
+    let moved = Strong::new(2);
+    let (alias, gone) = (kept.alias(), moved.alias());
+
+    tx.share(alias).ok().unwrap();
+    tx.send(moved).ok().unwrap();
+    mem::drop(tx);
+
+    thread::spawn(move || {
+        match rx.recv() {
+            Ok(Received::Shared(w)) => *w.write_blocking().unwrap() += 10,
+            _ => panic!(),
+        }
+        match rx.recv() {
+            Ok(Received::Moved(s)) => assert_eq!(*s.try_read().unwrap(), 2),
+            _ => panic!(),
+        }
+        assert!(rx.recv().is_err());
+        assert_eq!(rx.try_recv().err(), Some(TryRecvError::Disconnected));
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(*alias.read_blocking().unwrap(), 11);
+    assert!(dangling(gone.read_blocking().err()));
+
+    let (tx, rx) = channel::<u32>();
+    mem::drop(rx);
+    let back = match tx.send(Strong::new(3)) {
+        Err(err) => err.0,
+        Ok(()) => panic!(),
+    };
+    assert_eq!(*back.try_read().unwrap(), 3);
+
+    mem::drop((kept, back));
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn bounded_channel_blocks() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let (tx, rx) = bounded::<u32>(1);
+    tx.send(Strong::new(1)).ok().unwrap();
+
+    let sender = thread::spawn(move || {
+        tx.send(Strong::new(2)).ok().unwrap();
+    });
+    thread::sleep(Duration::from_millis(20));
+    assert!(!sender.is_finished());
+
+    let first = match rx.recv() {
+        Ok(Received::Moved(s)) => s,
+        _ => panic!(),
+    };
+    sender.join().unwrap();
+    assert_eq!(*first.try_read().unwrap(), 1);
+    assert!(matches!(rx.try_recv(), Ok(Received::Moved(_))));
+    assert_eq!(rx.try_recv().err(), Some(TryRecvError::Disconnected));
+
+    mem::drop(first);
+    GlobalGeneration::leak_all_and_reset();
+}
+
+#[test]
+fn select_receivers() {
+    let _lock = GLOBAL_TEST.lock();
+
+    let (tx1, rx1) = channel::<u32>();
+    let (tx2, rx2) = channel::<u32>();
+
+    assert_eq!(
+        select_timeout(&[&rx1, &rx2], Duration::from_millis(10)).err(),
+        Some(RecvTimeoutError::Timeout)
+    );
+
+    let sender = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        tx2.send(Strong::new(2)).ok().unwrap();
+    });
+    match select(&[&rx1, &rx2]) {
+        Ok((1, Received::Moved(s))) => assert_eq!(*s.try_read().unwrap(), 2),
+        _ => panic!(),
+    }
+    sender.join().unwrap();
+
+    assert_eq!(
+        select_timeout(&[&rx1, &rx2], Duration::from_millis(10)).err(),
+        Some(RecvTimeoutError::Timeout)
+    );
+    mem::drop(tx1);
+    assert!(select(&[&rx1, &rx2]).is_err());
+
+    GlobalGeneration::leak_all_and_reset();
 }
@@ -0,0 +1,81 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use super::Weak;
+
+pub struct WeakKeyMap<K: ?Sized + 'static, V> {
+    entries: HashMap<Weak<K>, V>,
+    sweep_at: usize,
+}
+
+const MIN_SWEEP: usize = 16;
+
+impl<K: ?Sized + 'static, V> WeakKeyMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            sweep_at: MIN_SWEEP,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn insert(&mut self, key: Weak<K>, value: V) -> Option<V> {
+        if !key.is_alive() {
+            return None;
+        }
+        if self.entries.len() >= self.sweep_at {
+            self.sweep();
+            self.sweep_at = MIN_SWEEP.max(self.entries.len() * 2);
+        }
+        self.entries.insert(key, value)
+    }
+
+    pub fn get(&mut self, key: &Weak<K>) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut(&mut self, key: &Weak<K>) -> Option<&mut V> {
+        match self.entries.entry(*key) {
+            Entry::Occupied(entry) if !key.is_alive() => {
+                entry.remove();
+                None
+            }
+            Entry::Occupied(entry) => Some(entry.into_mut()),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    pub fn contains_key(&mut self, key: &Weak<K>) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &Weak<K>) -> Option<V> {
+        let value = self.entries.remove(key)?;
+        key.is_alive().then_some(value)
+    }
+
+    pub fn sweep(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|key, _| key.is_alive());
+        before - self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Weak<K>, &V)> {
+        self.entries
+            .iter()
+            .filter(|(key, _)| key.is_alive())
+            .map(|(&key, value)| (key, value))
+    }
+}
+
+impl<K: ?Sized + 'static, V> Default for WeakKeyMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
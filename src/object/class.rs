@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::{const_rwlock, RwLock};
+
+use super::Symbol;
+use crate::interp::primitives::Primitive;
+use crate::syntax::MethodDefinition;
+
+pub(crate) struct Class {
+    pub(crate) name: Symbol,
+    pub(crate) superclass: Option<&'static Class>,
+    pub(crate) format: Format,
+    pub(crate) instance_variables: &'static [Symbol],
+    methods: RwLock<BTreeMap<Symbol, Method>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Format {
+    Immediate,
+    Boolean,
+    String,
+    Symbol,
+    Array,
+    Hash,
+    Record,
+    Bag,
+    Message,
+    Procedure,
+    Class,
+    OutChannel,
+    InChannel,
+    #[allow(dead_code)]
+    Extended,
+}
+
+#[derive(Clone)]
+pub(crate) enum Method {
+    Primitive(Primitive),
+    Compiled(Arc<MethodDefinition>),
+}
+
+impl Class {
+    const fn builtin(name: Symbol, superclass: Option<&'static Class>, format: Format) -> Self {
+        Self {
+            name,
+            superclass,
+            format,
+            instance_variables: &[],
+            methods: const_rwlock(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn subclass(
+        superclass: &'static Class,
+        name: Symbol,
+        instance_variables: &[Symbol],
+    ) -> Option<&'static Class> {
+        if superclass.format != Format::Record {
+            return None;
+        }
+        let mut layout = superclass.instance_variables.to_vec();
+        layout.extend_from_slice(instance_variables);
+        Some(Box::leak(Box::new(Self {
+            name,
+            superclass: Some(superclass),
+            format: Format::Record,
+            instance_variables: layout.leak(),
+            methods: RwLock::new(BTreeMap::new()),
+        })))
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn extension(superclass: &'static Class, name: Symbol) -> Option<&'static Class> {
+        if matches!(superclass.format, Format::Record | Format::Extended) {
+            return None;
+        }
+        Some(Box::leak(Box::new(Self {
+            name,
+            superclass: Some(superclass),
+            format: Format::Extended,
+            instance_variables: &[],
+            methods: RwLock::new(BTreeMap::new()),
+        })))
+    }
+
+    pub(crate) fn define(&self, selector: Symbol, method: Method) {
+        self.methods.write().insert(selector, method);
+    }
+
+    pub(crate) fn lookup(&'static self, selector: &str) -> Option<(&'static Class, Method)> {
+        let mut class = Some(self);
+        while let Some(c) = class {
+            if let Some(method) = c.methods.read().get(selector) {
+                return Some((c, method.clone()));
+            }
+            class = c.superclass;
+        }
+        None
+    }
+
+    pub(crate) fn inherits_from(&'static self, other: &'static Class) -> bool {
+        let mut class = Some(self);
+        while let Some(c) = class {
+            if std::ptr::eq(c, other) {
+                return true;
+            }
+            class = c.superclass;
+        }
+        false
+    }
+
+    pub(crate) fn instance_variable(&self, name: &str) -> Option<usize> {
+        self.instance_variables.iter().rposition(|&n| n == name)
+    }
+}
+
+pub(crate) static OBJECT: Class = Class::builtin("Object", None, Format::Record);
+
+pub(crate) static UNDEFINED_OBJECT: Class =
+    Class::builtin("UndefinedObject", Some(&OBJECT), Format::Immediate);
+
+pub(crate) static SMALL_INTEGER: Class =
+    Class::builtin("SmallInteger", Some(&OBJECT), Format::Immediate);
+
+pub(crate) static BOOLEAN: Class = Class::builtin("Boolean", Some(&OBJECT), Format::Boolean);
+
+pub(crate) static STRING: Class = Class::builtin("String", Some(&OBJECT), Format::String);
+
+pub(crate) static SYMBOL: Class = Class::builtin("Symbol", Some(&OBJECT), Format::Symbol);
+
+pub(crate) static ARRAY: Class = Class::builtin("Array", Some(&OBJECT), Format::Array);
+
+pub(crate) static DICTIONARY: Class = Class::builtin("Dictionary", Some(&OBJECT), Format::Hash);
+
+pub(crate) static BAG: Class = Class::builtin("Bag", Some(&OBJECT), Format::Bag);
+
+pub(crate) static MESSAGE: Class = Class::builtin("Message", Some(&OBJECT), Format::Message);
+
+pub(crate) static BLOCK_CLOSURE: Class =
+    Class::builtin("BlockClosure", Some(&OBJECT), Format::Procedure);
+
+pub(crate) static CLASS: Class = Class::builtin("Class", Some(&OBJECT), Format::Class);
+
+pub(crate) static OUT_CHANNEL: Class =
+    Class::builtin("OutChannel", Some(&OBJECT), Format::OutChannel);
+
+pub(crate) static IN_CHANNEL: Class = Class::builtin("InChannel", Some(&OBJECT), Format::InChannel);
+
+pub(crate) static SYSTEM_DICTIONARY: Class =
+    Class::builtin("SystemDictionary", Some(&OBJECT), Format::Record);
+
+pub(crate) static BUILTIN_CLASSES: [&Class; 15] = [
+    &OBJECT,
+    &UNDEFINED_OBJECT,
+    &SMALL_INTEGER,
+    &BOOLEAN,
+    &STRING,
+    &SYMBOL,
+    &ARRAY,
+    &DICTIONARY,
+    &BAG,
+    &MESSAGE,
+    &BLOCK_CLOSURE,
+    &CLASS,
+    &OUT_CHANNEL,
+    &IN_CHANNEL,
+    &SYSTEM_DICTIONARY,
+];
@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use parking_lot::RwLock;
+
+pub(crate) type Symbol = &'static str;
+
+pub(crate) struct Interner {
+    symbols: HashSet<Symbol>,
+    bytes: usize,
+}
+
+lazy_static::lazy_static! {
+    static ref INTERNER : RwLock<Interner> = RwLock::new(Interner {
+        symbols: HashSet::new(),
+        bytes: 0,
+    });
+}
+
+impl Interner {
+    pub(crate) fn intern(name: &str) -> Symbol {
+        if let Some(symbol) = Self::lookup(name) {
+            return symbol;
+        }
+        Self::insert(name, || Box::leak(name.to_string().into_boxed_str()))
+    }
+
+    pub(crate) fn intern_static(name: &'static str) -> Symbol {
+        if let Some(symbol) = Self::lookup(name) {
+            return symbol;
+        }
+        Self::insert(name, || name)
+    }
+
+    pub(crate) fn lookup(name: &str) -> Option<Symbol> {
+        INTERNER.read().symbols.get(name).copied()
+    }
+
+    pub(crate) fn count() -> usize {
+        INTERNER.read().symbols.len()
+    }
+
+    pub(crate) fn bytes() -> usize {
+        INTERNER.read().bytes
+    }
+
+    fn insert(name: &str, make: impl FnOnce() -> Symbol) -> Symbol {
+        let mut interner = INTERNER.write();
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = make();
+        interner.symbols.insert(symbol);
+        interner.bytes += symbol.len();
+        symbol
+    }
+}
+
+pub(crate) fn intern(name: &str) -> Symbol {
+    Interner::intern(name)
+}
+
+pub(crate) fn identical(a: Symbol, b: Symbol) -> bool {
+    std::ptr::eq(a, b)
+}
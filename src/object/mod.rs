@@ -1,18 +1,407 @@
-use std::sync::mpsc::*;
+use std::hash::Hash;
+use std::sync::Arc;
 use std::{collections::HashMap, mem::ManuallyDrop};
 
-use crate::memory::{Transferrable, Weak};
+use crate::interp::{bytecode::Code, Context};
+use crate::memory::{self, Receiver, Sender, Strong, Weak};
+use crate::syntax::Block;
 
 use self::slots::Slot;
 
+pub(crate) mod class;
+pub(crate) mod interner;
 pub(crate) mod slots;
+mod tests;
 
-struct Object {
+pub(crate) use class::{Class, Format, Method};
+pub(crate) use interner::{intern, Interner, Symbol};
+
+pub(crate) struct Object {
     class: &'static Class,
     data: ObjectUnion,
 }
 
-struct Class {}
+impl Object {
+    pub(crate) fn boolean(value: bool) -> Self {
+        Self {
+            class: &class::BOOLEAN,
+            data: ObjectUnion {
+                boolean: ManuallyDrop::new((value, Slot::nil())),
+            },
+        }
+    }
+
+    pub(crate) fn string(string: String) -> Self {
+        Self {
+            class: &class::STRING,
+            data: ObjectUnion {
+                string: ManuallyDrop::new(string),
+            },
+        }
+    }
+
+    pub(crate) fn symbol(symbol: Symbol) -> Self {
+        Self {
+            class: &class::SYMBOL,
+            data: ObjectUnion { symbol },
+        }
+    }
+
+    pub(crate) fn array(elements: Vec<Slot>) -> Self {
+        Self {
+            class: &class::ARRAY,
+            data: ObjectUnion {
+                array: ManuallyDrop::new(elements),
+            },
+        }
+    }
+
+    pub(crate) fn dictionary() -> Self {
+        Self {
+            class: &class::DICTIONARY,
+            data: ObjectUnion {
+                hash: ManuallyDrop::new(HashMap::new()),
+            },
+        }
+    }
+
+    pub(crate) fn record(class: &'static Class) -> Option<Self> {
+        if class.format != Format::Record {
+            return None;
+        }
+        let fields = class
+            .instance_variables
+            .iter()
+            .map(|_| Slot::nil())
+            .collect();
+        Some(Self {
+            class,
+            data: ObjectUnion {
+                record: ManuallyDrop::new(fields),
+            },
+        })
+    }
+
+    pub(crate) fn bag() -> Self {
+        Self {
+            class: &class::BAG,
+            data: ObjectUnion {
+                bag: ManuallyDrop::new(HashMap::new()),
+            },
+        }
+    }
+
+    pub(crate) fn message(selector: Symbol, arguments: Vec<Slot>) -> Self {
+        Self {
+            class: &class::MESSAGE,
+            data: ObjectUnion {
+                message: ManuallyDrop::new((selector, arguments, HashMap::new())),
+            },
+        }
+    }
+
+    pub(crate) fn procedure(procedure: Procedure) -> Self {
+        Self {
+            class: &class::BLOCK_CLOSURE,
+            data: ObjectUnion {
+                procedure: ManuallyDrop::new(procedure),
+            },
+        }
+    }
+
+    pub(crate) fn class_object(class: &'static Class) -> Self {
+        Self {
+            class: &class::CLASS,
+            data: ObjectUnion { class },
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn channel() -> (Self, Self) {
+        let (sender, receiver) = memory::channel();
+        let out_channel = Self {
+            class: &class::OUT_CHANNEL,
+            data: ObjectUnion {
+                out_channel: ManuallyDrop::new(sender),
+            },
+        };
+        let in_channel = Self {
+            class: &class::IN_CHANNEL,
+            data: ObjectUnion {
+                in_channel: ManuallyDrop::new(receiver),
+            },
+        };
+        (out_channel, in_channel)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn extended(
+        class: &'static Class,
+        base: &'static Class,
+        value: Slot,
+    ) -> Option<Self> {
+        if class.format != Format::Extended {
+            return None;
+        }
+        Some(Self {
+            class,
+            data: ObjectUnion {
+                extended: ManuallyDrop::new((base, value)),
+            },
+        })
+    }
+
+    pub(crate) fn instantiate(class: &'static Class) -> Option<Self> {
+        let mut object = match class.format {
+            Format::Record => return Self::record(class),
+            Format::String => Self::string(String::new()),
+            Format::Hash => Self::dictionary(),
+            Format::Bag => Self::bag(),
+            _ => return None,
+        };
+        object.class = class;
+        Some(object)
+    }
+
+    pub(crate) fn try_clone(&self) -> Option<Self> {
+        let data = unsafe {
+            match self.class.format {
+                Format::Immediate => return None,
+                Format::Boolean => ObjectUnion {
+                    boolean: ManuallyDrop::new((self.data.boolean.0, self.data.boolean.1.owned()?)),
+                },
+                Format::String => ObjectUnion {
+                    string: self.data.string.clone(),
+                },
+                Format::Symbol => ObjectUnion {
+                    symbol: self.data.symbol,
+                },
+                Format::Array => ObjectUnion {
+                    array: ManuallyDrop::new(owned_slots(&self.data.array)?),
+                },
+                Format::Hash => ObjectUnion {
+                    hash: ManuallyDrop::new(owned_values(&self.data.hash)?),
+                },
+                Format::Record => ObjectUnion {
+                    record: ManuallyDrop::new(owned_slots(&self.data.record)?),
+                },
+                Format::Bag => ObjectUnion {
+                    bag: ManuallyDrop::new(owned_values(&self.data.bag)?),
+                },
+                Format::Message => {
+                    let (selector, arguments, properties) = &*self.data.message;
+                    ObjectUnion {
+                        message: ManuallyDrop::new((
+                            selector,
+                            owned_slots(arguments)?,
+                            owned_values(properties)?,
+                        )),
+                    }
+                }
+                Format::Procedure => ObjectUnion {
+                    procedure: ManuallyDrop::new(self.data.procedure.try_clone()?),
+                },
+                Format::Class => ObjectUnion {
+                    class: self.data.class,
+                },
+                Format::OutChannel => ObjectUnion {
+                    out_channel: self.data.out_channel.clone(),
+                },
+                Format::InChannel => return None,
+                Format::Extended => {
+                    let (base, value) = &*self.data.extended;
+                    ObjectUnion {
+                        extended: ManuallyDrop::new((base, value.owned()?)),
+                    }
+                }
+            }
+        };
+        Some(Self {
+            class: self.class,
+            data,
+        })
+    }
+
+    pub(crate) fn class(&self) -> &'static Class {
+        self.class
+    }
+
+    pub(crate) fn as_boolean(&self) -> Option<bool> {
+        match self.class.format {
+            Format::Boolean => Some(unsafe { self.data.boolean.0 }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_string(&self) -> Option<&String> {
+        match self.class.format {
+            Format::String => Some(unsafe { &self.data.string }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn as_string_mut(&mut self) -> Option<&mut String> {
+        match self.class.format {
+            Format::String => Some(unsafe { &mut self.data.string }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_symbol(&self) -> Option<Symbol> {
+        match self.class.format {
+            Format::Symbol => Some(unsafe { self.data.symbol }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&Vec<Slot>> {
+        match self.class.format {
+            Format::Array => Some(unsafe { &self.data.array }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array_mut(&mut self) -> Option<&mut Vec<Slot>> {
+        match self.class.format {
+            Format::Array => Some(unsafe { &mut self.data.array }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn as_hash(&self) -> Option<&HashMap<Weak<Object>, Slot>> {
+        match self.class.format {
+            Format::Hash => Some(unsafe { &self.data.hash }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn as_hash_mut(&mut self) -> Option<&mut HashMap<Weak<Object>, Slot>> {
+        match self.class.format {
+            Format::Hash => Some(unsafe { &mut self.data.hash }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_record(&self) -> Option<&Vec<Slot>> {
+        match self.class.format {
+            Format::Record => Some(unsafe { &self.data.record }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_record_mut(&mut self) -> Option<&mut Vec<Slot>> {
+        match self.class.format {
+            Format::Record => Some(unsafe { &mut self.data.record }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_bag(&self) -> Option<&HashMap<Symbol, Slot>> {
+        match self.class.format {
+            Format::Bag => Some(unsafe { &self.data.bag }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_bag_mut(&mut self) -> Option<&mut HashMap<Symbol, Slot>> {
+        match self.class.format {
+            Format::Bag => Some(unsafe { &mut self.data.bag }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_message(&self) -> Option<(Symbol, &Vec<Slot>)> {
+        match self.class.format {
+            Format::Message => {
+                let (selector, arguments, _) = unsafe { &*self.data.message };
+                Some((selector, arguments))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_procedure(&self) -> Option<&Procedure> {
+        match self.class.format {
+            Format::Procedure => Some(unsafe { &self.data.procedure }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_procedure_mut(&mut self) -> Option<&mut Procedure> {
+        match self.class.format {
+            Format::Procedure => Some(unsafe { &mut self.data.procedure }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_class(&self) -> Option<&'static Class> {
+        match self.class.format {
+            Format::Class => Some(unsafe { self.data.class }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn as_out_channel(&self) -> Option<&Sender<Slot>> {
+        match self.class.format {
+            Format::OutChannel => Some(unsafe { &self.data.out_channel }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn as_in_channel(&self) -> Option<&Receiver<Slot>> {
+        match self.class.format {
+            Format::InChannel => Some(unsafe { &self.data.in_channel }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn as_extended(&self) -> Option<(&'static Class, &Slot)> {
+        match self.class.format {
+            Format::Extended => {
+                let (base, value) = unsafe { &*self.data.extended };
+                Some((base, value))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        unsafe {
+            match self.class.format {
+                Format::Immediate | Format::Symbol | Format::Class => {}
+                Format::Boolean => ManuallyDrop::drop(&mut self.data.boolean),
+                Format::String => ManuallyDrop::drop(&mut self.data.string),
+                Format::Array => ManuallyDrop::drop(&mut self.data.array),
+                Format::Hash => ManuallyDrop::drop(&mut self.data.hash),
+                Format::Record => ManuallyDrop::drop(&mut self.data.record),
+                Format::Bag => ManuallyDrop::drop(&mut self.data.bag),
+                Format::Message => ManuallyDrop::drop(&mut self.data.message),
+                Format::Procedure => ManuallyDrop::drop(&mut self.data.procedure),
+                Format::OutChannel => ManuallyDrop::drop(&mut self.data.out_channel),
+                Format::InChannel => ManuallyDrop::drop(&mut self.data.in_channel),
+                Format::Extended => ManuallyDrop::drop(&mut self.data.extended),
+            }
+        }
+    }
+}
+
+fn owned_slots(slots: &[Slot]) -> Option<Vec<Slot>> {
+    slots.iter().map(Slot::owned).collect()
+}
+
+fn owned_values<K: Copy + Eq + Hash>(map: &HashMap<K, Slot>) -> Option<HashMap<K, Slot>> {
+    map.iter()
+        .map(|(&key, value)| Some((key, value.owned()?)))
+        .collect()
+}
 
 union ObjectUnion {
     boolean: ManuallyDrop<(bool, Slot)>,
@@ -25,14 +414,55 @@ union ObjectUnion {
     message: ManuallyDrop<(Symbol, Vec<Slot>, HashMap<Symbol, Slot>)>,
     procedure: ManuallyDrop<Procedure>,
     class: &'static Class,
-    out_channel: ManuallyDrop<Sender<Transferrable<Slot>>>,
-    in_channel: ManuallyDrop<Receiver<Transferrable<Slot>>>,
-    file: usize,
-    thread: usize,
+    out_channel: ManuallyDrop<Sender<Slot>>,
+    in_channel: ManuallyDrop<Receiver<Slot>>,
     extended: ManuallyDrop<(&'static Class, Slot)>,
 }
 
-type Symbol = &'static str;
-struct Interner {}
+pub(crate) struct Procedure {
+    pub(crate) parameters: Vec<Symbol>,
+    pub(crate) block: Arc<Block>,
+    pub(crate) code: Option<Arc<Code>>,
+    pub(crate) outer: Weak<Context>,
+    pub(crate) captured: Option<Strong<Context>>,
+    pub(crate) home: usize,
+}
+
+impl Procedure {
+    pub(crate) fn new(block: Arc<Block>, outer: Weak<Context>, home: usize) -> Self {
+        Self {
+            parameters: block.parameters.iter().map(|p| intern(p)).collect(),
+            block,
+            code: None,
+            outer,
+            captured: None,
+            home,
+        }
+    }
+
+    fn try_clone(&self) -> Option<Self> {
+        if self.captured.is_some() {
+            return None;
+        }
+        Some(Self {
+            parameters: self.parameters.clone(),
+            block: self.block.clone(),
+            code: self.code.clone(),
+            outer: self.outer,
+            captured: None,
+            home: self.home,
+        })
+    }
 
-struct Procedure {}
+    pub(crate) fn arity(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub(crate) fn capture(&mut self, context: Strong<Context>) -> Result<(), Strong<Context>> {
+        if self.captured.is_some() || !self.outer.ptr_eq(&context.alias()) {
+            return Err(context);
+        }
+        self.captured = Some(context);
+        Ok(())
+    }
+}
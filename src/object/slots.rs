@@ -1,8 +1,8 @@
 use crate::object::*;
-use std::mem;
-use std::mem::ManuallyDrop;
+use std::{fmt, mem};
 
 use crate::memory::{
+    counter::Count,
     pointers::{LocalOrGlobal, OwnershipBit, RawRef},
     Strong, Weak,
 };
@@ -17,20 +17,138 @@ impl Drop for Slot {
 }
 
 #[derive(Clone, Copy)]
-union RawSlot {
+pub(crate) union RawSlot {
     int: Int,
     raw: RawRef<Object>,
+    bytes: [u8; SLOT_BYTES],
 }
 
+pub(crate) const SLOT_BYTES: usize = mem::size_of::<Int>();
+const DISCRIMINANT: usize = mem::offset_of!(Int, discriminant);
+const OWNERSHIP: usize = mem::offset_of!(Int, ownership);
+
+impl RawSlot {
+    #[cfg(test)]
+    pub(crate) fn from_bytes(bytes: [u8; SLOT_BYTES]) -> Self {
+        RawSlot { bytes }
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; SLOT_BYTES] {
+        unsafe { self.bytes }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SlotError {
+    BadDiscriminant(u8),
+    BadOwnership(u8),
+    NonCanonicalNil,
+    NullPointer,
+}
+
+impl fmt::Display for SlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotError::BadDiscriminant(tag) => write!(f, "bad slot discriminant {tag}"),
+            SlotError::BadOwnership(tag) => write!(f, "bad slot ownership bit {tag}"),
+            SlotError::NonCanonicalNil => f.write_str("non-canonical nil slot"),
+            SlotError::NullPointer => f.write_str("null pointer in reference slot"),
+        }
+    }
+}
+
+impl std::error::Error for SlotError {}
+
 #[derive(Copy, Clone)]
-#[repr(C)]
+#[repr(C, packed(8))]
 struct Int {
     val: i128,
-    nonzero: u32,
+    nonzero: Count,
     discriminant: LocalOrGlobal,
     ownership: OwnershipBit,
 }
 
+impl Slot {
+    pub(crate) fn nil() -> Self {
+        SlotEnum::Nil.into()
+    }
+
+    pub(crate) fn int(val: i128) -> Self {
+        SlotEnum::Int(val).into()
+    }
+
+    pub(crate) fn is_nil(&self) -> bool {
+        let Int {
+            val,
+            nonzero,
+            discriminant,
+            ..
+        } = unsafe { self.0.int };
+        val == 0 && nonzero == 0 && discriminant == LocalOrGlobal::Neither
+    }
+
+    pub(crate) fn as_int(&self) -> Option<i128> {
+        let Int {
+            val,
+            nonzero,
+            discriminant,
+            ..
+        } = unsafe { self.0.int };
+        (nonzero != 0 && discriminant == LocalOrGlobal::Neither).then_some(val)
+    }
+
+    pub(crate) fn is_strong(&self) -> bool {
+        let Int {
+            discriminant,
+            ownership,
+            ..
+        } = unsafe { self.0.int };
+        discriminant != LocalOrGlobal::Neither && ownership == OwnershipBit::Strong
+    }
+
+    pub(crate) fn object(&self) -> Option<Weak<Object>> {
+        let Int { discriminant, .. } = unsafe { self.0.int };
+        (discriminant != LocalOrGlobal::Neither).then(|| unsafe { Weak::from_raw(self.0.raw) })
+    }
+
+    pub(crate) fn alias(&self) -> Slot {
+        match (self.as_int(), self.object()) {
+            (Some(val), _) => Slot::int(val),
+            (None, Some(object)) => Slot::from(object),
+            (None, None) => Slot::nil(),
+        }
+    }
+
+    pub(crate) fn owned(&self) -> Option<Slot> {
+        match self.is_strong() {
+            true => self.try_clone_strong(),
+            false => Some(self.alias()),
+        }
+    }
+
+    pub(crate) fn try_clone_strong(&self) -> Option<Slot> {
+        match self.object() {
+            Some(object) => {
+                let copy = object.try_read().ok()?.try_clone()?;
+                Some(Strong::new(copy).into())
+            }
+            None => Some(self.alias()),
+        }
+    }
+}
+
+impl From<Strong<Object>> for Slot {
+    fn from(it: Strong<Object>) -> Self {
+        SlotEnum::Strong(it).into()
+    }
+}
+
+impl From<Weak<Object>> for Slot {
+    fn from(it: Weak<Object>) -> Self {
+        SlotEnum::Weak(it).into()
+    }
+}
+
 impl From<SlotEnum> for Slot {
     fn from(it: SlotEnum) -> Self {
         Self(it.into())
@@ -63,12 +181,11 @@ impl From<SlotEnum> for RawSlot {
 }
 
 impl SlotEnum {
-    fn from_raw(it: RawRef<Object>) -> Self {
+    fn from_raw(it: RawRef<Object>) -> Result<Self, SlotError> {
         match it.ownership {
-            Integer => panic!("Nil ownership"),
-            Inferred => panic!("Inferred ownership"),
-            OwnershipBit::Weak => Self::Weak(unsafe { Weak::from_raw(it) }),
-            OwnershipBit::Strong => Self::Strong(unsafe { Strong::from_raw(it) }),
+            OwnershipBit::Weak => Ok(Self::Weak(unsafe { Weak::from_raw(it) })),
+            OwnershipBit::Strong => Ok(Self::Strong(unsafe { Strong::from_raw(it) })),
+            tag => Err(SlotError::BadOwnership(tag as u8)),
         }
     }
 }
@@ -83,39 +200,51 @@ impl From<Slot> for SlotEnum {
 
 impl From<RawSlot> for SlotEnum {
     fn from(it: RawSlot) -> Self {
-        use LocalOrGlobal::*;
-        use OwnershipBit::*;
-        unsafe {
-            match it {
-                RawSlot {
-                    int:
-                        Int {
-                            val,
-                            nonzero: 1..,
-                            discriminant: Neither,
-                            ownership: Copy,
-                        },
-                } => SlotEnum::Int(val),
-                RawSlot {
-                    int:
-                        Int {
-                            val: 0,
-                            nonzero: 0,
-                            discriminant: Neither,
-                            ownership: Copy,
-                        },
-                } => SlotEnum::Nil,
-                RawSlot {
-                    int:
-                        Int {
-                            discriminant: Local | Global,
-                            ownership: Weak | Strong,
-                            ..
-                        },
-                } => SlotEnum::from_raw(it.raw),
-                _ => panic!(),
+        match Self::checked(it) {
+            Ok(slot) => slot,
+            Err(err) => panic!("{err}"),
+        }
+    }
+}
+
+impl SlotEnum {
+    fn checked(it: RawSlot) -> Result<Self, SlotError> {
+        let bytes = it.to_bytes();
+        let (discriminant, ownership) = (bytes[DISCRIMINANT], bytes[OWNERSHIP]);
+        if discriminant > LocalOrGlobal::Global as u8 {
+            return Err(SlotError::BadDiscriminant(discriminant));
+        }
+        if ownership > OwnershipBit::Inferred as u8 {
+            return Err(SlotError::BadOwnership(ownership));
+        }
+
+        if discriminant == LocalOrGlobal::Neither as u8 {
+            if ownership != OwnershipBit::Copy as u8 {
+                return Err(SlotError::BadOwnership(ownership));
             }
+            let Int { val, nonzero, .. } = unsafe { it.int };
+            return match (val, nonzero) {
+                (val, 1..) => Ok(SlotEnum::Int(val)),
+                (0, 0) => Ok(SlotEnum::Nil),
+                _ => Err(SlotError::NonCanonicalNil),
+            };
+        }
+
+        if bytes[..16]
+            .chunks(8)
+            .any(|word| word.iter().all(|&b| b == 0))
+        {
+            return Err(SlotError::NullPointer);
         }
+        SlotEnum::from_raw(unsafe { it.raw })
+    }
+}
+
+impl TryFrom<RawSlot> for Slot {
+    type Error = SlotError;
+
+    fn try_from(it: RawSlot) -> Result<Self, SlotError> {
+        SlotEnum::checked(it).map(Self::from)
     }
 }
 
@@ -127,6 +256,132 @@ enum SlotEnum {
 }
 
 #[test]
+#[cfg(not(feature = "wide-generations"))]
 fn slot_size() {
-    assert_eq!(mem::size_of::<RawSlot>(), 3 * mem::size_of::<usize>())
+    assert_eq!(mem::size_of::<RawSlot>(), 3 * mem::size_of::<usize>());
+    assert_eq!((DISCRIMINANT, OWNERSHIP), (20, 21));
+}
+
+#[test]
+#[cfg(feature = "wide-generations")]
+fn slot_size() {
+    assert_eq!(mem::size_of::<RawSlot>(), 4 * mem::size_of::<usize>());
+    assert_eq!((DISCRIMINANT, OWNERSHIP), (24, 25));
+}
+
+#[test]
+fn slot_layout_matches_references() {
+    assert_eq!(mem::size_of::<RawRef<Object>>(), SLOT_BYTES);
+    assert_eq!(mem::offset_of!(RawRef<Object>, discriminant), DISCRIMINANT);
+    assert_eq!(mem::offset_of!(RawRef<Object>, ownership), OWNERSHIP);
+}
+
+#[cfg(test)]
+fn raw_bytes(slot: Slot) -> [u8; SLOT_BYTES] {
+    let bytes = slot.0.to_bytes();
+    mem::forget(slot);
+    bytes
+}
+
+#[test]
+fn slot_round_trip() {
+    let bytes = raw_bytes(Slot::int(-42));
+    let slot = Slot::try_from(RawSlot::from_bytes(bytes)).unwrap();
+    assert_eq!(slot.as_int(), Some(-42));
+
+    let bytes = raw_bytes(Slot::nil());
+    assert!(Slot::try_from(RawSlot::from_bytes(bytes)).unwrap().is_nil());
+
+    let strong = Strong::new(Object::boolean(true));
+    let bytes = raw_bytes(strong.alias().into());
+    let slot = Slot::try_from(RawSlot::from_bytes(bytes)).unwrap();
+    assert!(!slot.is_strong());
+    assert!(slot.object().unwrap().ptr_eq(&strong.alias()));
+}
+
+#[test]
+fn slot_errors() {
+    let check = |bytes| Slot::try_from(RawSlot::from_bytes(bytes)).err();
+
+    let mut bytes = raw_bytes(Slot::int(1));
+    bytes[DISCRIMINANT] = 7;
+    assert_eq!(check(bytes), Some(SlotError::BadDiscriminant(7)));
+
+    let mut bytes = raw_bytes(Slot::int(1));
+    bytes[OWNERSHIP] = 9;
+    assert_eq!(check(bytes), Some(SlotError::BadOwnership(9)));
+
+    let mut bytes = raw_bytes(Slot::int(1));
+    bytes[OWNERSHIP] = OwnershipBit::Strong as u8;
+    assert_eq!(check(bytes), Some(SlotError::BadOwnership(2)));
+
+    let mut bytes = raw_bytes(Slot::nil());
+    bytes[3] = 1;
+    assert_eq!(check(bytes), Some(SlotError::NonCanonicalNil));
+
+    let mut bytes = raw_bytes(Slot::nil());
+    bytes[DISCRIMINANT] = LocalOrGlobal::Local as u8;
+    bytes[OWNERSHIP] = OwnershipBit::Weak as u8;
+    assert_eq!(check(bytes), Some(SlotError::NullPointer));
+
+    let strong = Strong::new(Object::boolean(true));
+    let mut bytes = raw_bytes(strong.alias().into());
+    bytes[OWNERSHIP] = OwnershipBit::Inferred as u8;
+    assert_eq!(check(bytes), Some(SlotError::BadOwnership(3)));
+    assert_eq!(
+        SlotError::BadOwnership(3).to_string(),
+        "bad slot ownership bit 3"
+    );
+}
+
+#[test]
+fn alias_and_clone() {
+    let int = Slot::int(5);
+    assert_eq!(int.alias().as_int(), Some(5));
+    assert_eq!(int.try_clone_strong().unwrap().as_int(), Some(5));
+    assert!(Slot::nil().alias().is_nil());
+
+    let strong: Slot = Strong::new(Object::string("abc".to_string())).into();
+    let alias = strong.alias();
+    assert!(!alias.is_strong());
+    assert!(alias.object().unwrap().ptr_eq(&strong.object().unwrap()));
+
+    let copy = alias.try_clone_strong().unwrap();
+    assert!(copy.is_strong());
+    assert!(!copy.object().unwrap().ptr_eq(&strong.object().unwrap()));
+    let read = copy.object().unwrap();
+    assert_eq!(read.try_read().unwrap().as_string().unwrap(), "abc");
+
+    drop(strong);
+    assert!(alias.object().unwrap().try_read().is_err());
+    assert!(alias.try_clone_strong().is_none());
+}
+
+#[test]
+fn clone_copies_owned_children() {
+    let child = Strong::new(Object::string("child".to_string()));
+    let shared = Strong::new(Object::string("shared".to_string()));
+    let array: Slot = Strong::new(Object::array(vec![
+        child.into(),
+        shared.alias().into(),
+        Slot::int(3),
+    ]))
+    .into();
+
+    let copy = array.try_clone_strong().unwrap();
+    let original = array.object().unwrap();
+    let original = original.try_read().unwrap();
+    let original = original.as_array().unwrap();
+    let copied = copy.object().unwrap();
+    let copied = copied.try_read().unwrap();
+    let copied = copied.as_array().unwrap();
+
+    assert!(copied[0].is_strong());
+    assert!(!copied[0]
+        .object()
+        .unwrap()
+        .ptr_eq(&original[0].object().unwrap()));
+    assert!(!copied[1].is_strong());
+    assert!(copied[1].object().unwrap().ptr_eq(&shared.alias()));
+    assert_eq!(copied[2].as_int(), Some(3));
 }
@@ -0,0 +1,234 @@
+#[cfg(test)]
+use std::thread;
+
+#[cfg(test)]
+use std::sync::mpsc::TryRecvError;
+
+#[cfg(test)]
+use super::{class, slots::Slot, Class, Object};
+
+#[cfg(test)]
+use crate::memory::{Reading, Strong, Weak, Writing};
+
+#[cfg(test)]
+use super::interner::*;
+
+#[test]
+fn equal_symbols_are_identical() {
+    let a = intern("interner:identity:");
+    let b = intern(&String::from("interner:identity:"));
+    assert!(identical(a, b));
+    assert!(!identical(a, intern("interner:other:")));
+}
+
+#[test]
+fn lookup_does_not_insert() {
+    assert_eq!(Interner::lookup("interner:never:interned"), None);
+    assert_eq!(Interner::lookup("interner:never:interned"), None);
+
+    let symbol = intern("interner:looked:up");
+    assert!(identical(
+        Interner::lookup("interner:looked:up").unwrap(),
+        symbol
+    ));
+}
+
+#[test]
+fn static_symbols_are_not_copied() {
+    static NAME: &str = "interner:static:name";
+    assert!(identical(Interner::intern_static(NAME), NAME));
+    assert!(identical(intern("interner:static:name"), NAME));
+}
+
+#[test]
+fn counts_track_growth() {
+    let count = Interner::count();
+    let bytes = Interner::bytes();
+    intern("interner:counted");
+    assert!(Interner::count() > count);
+    assert!(Interner::bytes() >= bytes + "interner:counted".len());
+}
+
+#[test]
+fn interning_across_threads() {
+    let symbols: Vec<usize> = (0..8)
+        .map(|_| thread::spawn(|| intern("interner:threaded").as_ptr() as usize))
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert!(symbols.iter().all(|&p| p == symbols[0]));
+}
+
+#[cfg(test)]
+fn sentinel() -> (Slot, Weak<Object>) {
+    let strong = Strong::new(Object::symbol(intern("sentinel")));
+    let weak = strong.alias();
+    (strong.into(), weak)
+}
+
+#[test]
+fn accessors_follow_the_class() {
+    let string = Object::string("abc".to_string());
+    assert_eq!(string.as_string().unwrap(), "abc");
+    assert!(string.as_symbol().is_none());
+    assert!(string.as_array().is_none());
+
+    let symbol = Object::symbol(intern("abc"));
+    assert_eq!(symbol.as_symbol(), Some("abc"));
+    assert!(symbol.as_string().is_none());
+
+    assert_eq!(Object::boolean(true).as_boolean(), Some(true));
+    assert!(Object::dictionary().as_hash().unwrap().is_empty());
+    assert!(Object::bag().as_bag().unwrap().is_empty());
+    assert!(Object::dictionary().as_bag().is_none());
+    assert!(Object::record(&class::OBJECT)
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .is_empty());
+    assert!(Object::record(&class::ARRAY).is_none());
+    assert!(std::ptr::eq(
+        Object::class_object(&class::ARRAY).as_class().unwrap(),
+        &class::ARRAY
+    ));
+
+    let (out_channel, in_channel) = Object::channel();
+    assert!(out_channel.as_out_channel().is_some());
+    assert!(out_channel.as_in_channel().is_none());
+    assert!(in_channel.as_in_channel().is_some());
+}
+
+#[test]
+fn mutable_accessors() {
+    let mut string = Object::string("ab".to_string());
+    string.as_string_mut().unwrap().push('c');
+    assert_eq!(string.as_string().unwrap(), "abc");
+
+    let mut bag = Object::bag();
+    bag.as_bag_mut()
+        .unwrap()
+        .insert(intern("key"), Slot::int(3));
+    assert_eq!(bag.as_bag().unwrap()[intern("key")].as_int(), Some(3));
+    assert!(bag.as_array_mut().is_none());
+
+    let mut array = Object::array(vec![Slot::nil()]);
+    array.as_array_mut().unwrap()[0] = Slot::int(7);
+    assert_eq!(array.as_array().unwrap()[0].as_int(), Some(7));
+}
+
+#[test]
+fn instantiate_by_format() {
+    assert!(Object::instantiate(&class::STRING)
+        .unwrap()
+        .as_string()
+        .is_some());
+    assert!(Object::instantiate(&class::DICTIONARY)
+        .unwrap()
+        .as_hash()
+        .is_some());
+    assert!(Object::instantiate(&class::BAG).unwrap().as_bag().is_some());
+    assert!(Object::instantiate(&class::OBJECT)
+        .unwrap()
+        .as_record()
+        .is_some());
+    assert!(Object::instantiate(&class::SYMBOL).is_none());
+    assert!(Object::instantiate(&class::BOOLEAN).is_none());
+}
+
+#[test]
+fn extended_objects() {
+    let boxed = Class::extension(&class::STRING, intern("BoxedString")).unwrap();
+    assert!(Class::extension(&class::OBJECT, intern("Nope")).is_none());
+    assert!(Object::extended(&class::OBJECT, &class::STRING, Slot::nil()).is_none());
+
+    let (slot, weak) = sentinel();
+    let object = Object::extended(boxed, &class::STRING, slot).unwrap();
+    let (base, value) = object.as_extended().unwrap();
+    assert!(std::ptr::eq(base, &class::STRING));
+    assert!(value.is_strong());
+    assert!(object.as_string().is_none());
+
+    drop(object);
+    assert!(weak.try_read().is_err());
+}
+
+#[test]
+fn drop_releases_owned_slots() {
+    let containers: Vec<fn(Slot) -> Object> = vec![
+        |slot| Object::array(vec![slot]),
+        |slot| Object::message(intern("at:"), vec![slot]),
+        |slot| {
+            let mut bag = Object::bag();
+            bag.as_bag_mut().unwrap().insert(intern("key"), slot);
+            bag
+        },
+        |slot| {
+            let mut record = Object::record(&class::OBJECT).unwrap();
+            record.as_record_mut().unwrap().push(slot);
+            record
+        },
+    ];
+    for container in containers {
+        let (slot, weak) = sentinel();
+        let object = container(slot);
+        assert!(weak.try_read().is_ok());
+        drop(object);
+        assert!(weak.try_read().is_err());
+    }
+}
+
+#[test]
+fn drop_disconnects_channels() {
+    let (out_channel, in_channel) = Object::channel();
+    assert_eq!(
+        in_channel.as_in_channel().unwrap().try_recv().err(),
+        Some(TryRecvError::Empty)
+    );
+    drop(out_channel);
+    assert_eq!(
+        in_channel.as_in_channel().unwrap().try_recv().err(),
+        Some(TryRecvError::Disconnected)
+    );
+}
+
+#[test]
+fn mapped_array_element() {
+    let array = Strong::new(Object::array(vec![Slot::int(1), Slot::int(2)]));
+
+    let first = Reading::map(array.try_read().unwrap(), |o| &o.as_array().unwrap()[0]);
+    assert_eq!(first.as_int(), Some(1));
+    assert!(array.try_write().is_err());
+    drop(first);
+
+    let mut second = Writing::map(array.try_write().unwrap(), |o| {
+        &mut o.as_array_mut().unwrap()[1]
+    });
+    *second = Slot::int(3);
+    drop(second);
+
+    let elements = array.try_read().unwrap();
+    assert_eq!(elements.as_array().unwrap()[1].as_int(), Some(3));
+}
+
+#[test]
+fn clone_dictionary() {
+    let key = Strong::new(Object::symbol(intern("key")));
+    let (value, weak) = sentinel();
+
+    let mut dictionary = Object::dictionary();
+    let hash = dictionary.as_hash_mut().unwrap();
+    hash.insert(key.alias(), value);
+    hash.insert(weak, Slot::int(1));
+
+    let copy = dictionary.try_clone().unwrap();
+    let copied = copy.as_hash().unwrap();
+    assert_eq!(copied.len(), 2);
+    assert_eq!(copied[&weak].as_int(), Some(1));
+
+    let value = copied[&key.alias()].object().unwrap();
+    assert!(!value.ptr_eq(&weak));
+    assert_eq!(value.try_read().unwrap().as_symbol(), Some("sentinel"));
+
+    drop(dictionary);
+    assert!(weak.try_read().is_err());
+    assert!(value.try_read().is_ok());
+}
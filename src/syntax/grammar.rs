@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use super::*;
+
+fn fold_unary(receiver: Expr, messages: Vec<(usize, String, usize)>) -> Expr {
+    messages
+        .into_iter()
+        .fold(receiver, |receiver, (start, selector, end)| {
+            let span = Span::new(receiver.span.start, end);
+            Expr {
+                kind: ExprKind::Send(
+                    Box::new(receiver),
+                    Message {
+                        selector,
+                        arguments: vec![],
+                        span: Span::new(start, end),
+                    },
+                ),
+                span,
+            }
+        })
+}
+
+fn fold_binary(receiver: Expr, messages: Vec<(usize, String, Expr)>) -> Expr {
+    messages
+        .into_iter()
+        .fold(receiver, |receiver, (start, selector, argument)| {
+            let span = Span::new(receiver.span.start, argument.span.end);
+            let message_span = Span::new(start, argument.span.end);
+            Expr {
+                kind: ExprKind::Send(
+                    Box::new(receiver),
+                    Message {
+                        selector,
+                        arguments: vec![argument],
+                        span: message_span,
+                    },
+                ),
+                span,
+            }
+        })
+}
+
+fn keyword_message(parts: Vec<(usize, String, Expr)>) -> Option<Message> {
+    let start = parts.first()?.0;
+    let end = parts.last()?.2.span.end;
+    let mut selector = String::new();
+    let mut arguments = Vec::with_capacity(parts.len());
+    for (_, keyword, argument) in parts {
+        selector.push_str(&keyword);
+        arguments.push(argument);
+    }
+    Some(Message {
+        selector,
+        arguments,
+        span: Span::new(start, end),
+    })
+}
+
+fn integer(sign: Option<&str>, digits: &str, radix: u32) -> Result<i128, &'static str> {
+    let digits = format!("{}{digits}", sign.unwrap_or_default());
+    i128::from_str_radix(&digits, radix).map_err(|_| OUT_OF_RANGE)
+}
+
+peg::parser! {
+    pub(crate) grammar aloxtalk() for str {
+        pub(crate) rule program() -> Body
+            = _ b:body() _ { b }
+
+        pub(crate) rule expression() -> Expr
+            = assignment()
+            / cascade()
+
+        rule body() -> Body
+            = s:position!() t:temporaries()? _ st:statements() e:position!() {
+                Body {
+                    temporaries: t.unwrap_or_default(),
+                    statements: st,
+                    span: Span::new(s, e),
+                }
+            }
+
+        rule temporaries() -> Vec<String>
+            = "|" _ t:(n:identifier() _ { n })* "|" { t }
+
+        rule statements() -> Vec<Statement>
+            = st:(statement() ** (_ "." _)) (_ ".")* { st }
+
+        rule statement() -> Statement
+            = c:class_definition() { Statement::Expression(c) }
+            / s:position!() "^" _ e:expression() {
+                let span = Span::new(s, e.span.end);
+                Statement::Return(e, span)
+            }
+            / e:expression() { Statement::Expression(e) }
+
+        rule class_definition() -> Expr
+            = s:position!() sup:identifier() _ "subclass:" _ n:identifier() _ "[" _
+              iv:temporaries()? _ ms:methods() _ "]" e:position!() {
+                let class = ClassDefinition {
+                    name: n,
+                    superclass: Some(sup),
+                    instance_variables: iv.unwrap_or_default(),
+                    methods: ms,
+                    span: Span::new(s, e),
+                };
+                Expr::new(ExprKind::Class(Arc::new(class)), s, e)
+            }
+            / s:position!() n:identifier() _ "extend" !alphanumeric() _ "[" _ ms:methods() _ "]" e:position!() {
+                let class = ClassDefinition {
+                    name: n,
+                    superclass: None,
+                    instance_variables: vec![],
+                    methods: ms,
+                    span: Span::new(s, e),
+                };
+                Expr::new(ExprKind::Class(Arc::new(class)), s, e)
+            }
+
+        rule methods() -> Vec<Arc<MethodDefinition>>
+            = (m:method_definition() _ { m })*
+
+        rule method_definition() -> Arc<MethodDefinition>
+            = s:position!() p:method_pattern() _ "[" _ b:body() _ "]" e:position!() {
+                let (selector, parameters) = p;
+                Arc::new(MethodDefinition { selector, parameters, body: b, span: Span::new(s, e) })
+            }
+
+        rule method_pattern() -> (String, Vec<String>)
+            = parts:(k:keyword() _ a:identifier() _ { (k, a) })+ {
+                let (keywords, parameters): (Vec<String>, Vec<String>) = parts.into_iter().unzip();
+                (keywords.concat(), parameters)
+            }
+            / o:binary_operator() _ a:identifier() { (o, vec![a]) }
+            / n:identifier() { (n, vec![]) }
+
+        rule assignment() -> Expr
+            = s:position!() n:identifier() _ ":=" _ v:expression() e:position!() {
+                Expr::new(ExprKind::Assign(n, Box::new(v)), s, e)
+            }
+
+        rule cascade() -> Expr
+            = s:position!() k:keyword_send() rest:(_ ";" _ m:cascade_message() { m })* e:position!() {?
+                if rest.is_empty() {
+                    return Ok(k);
+                }
+                match k.kind {
+                    ExprKind::Send(receiver, first) => {
+                        let mut messages = vec![first];
+                        messages.extend(rest);
+                        Ok(Expr::new(ExprKind::Cascade(receiver, messages), s, e))
+                    }
+                    _ => Err("message send before cascade"),
+                }
+            }
+
+        rule cascade_message() -> Message
+            = parts:keyword_parts() {? keyword_message(parts).ok_or("keyword message") }
+            / s:position!() o:binary_operator() _ a:unary_send() {
+                let span = Span::new(s, a.span.end);
+                Message { selector: o, arguments: vec![a], span }
+            }
+            / s:position!() n:identifier() e:position!() {
+                Message { selector: n, arguments: vec![], span: Span::new(s, e) }
+            }
+
+        rule keyword_send() -> Expr
+            = r:binary_send() parts:(_ p:keyword_parts() { p })? {
+                match parts.and_then(keyword_message) {
+                    Some(message) => {
+                        let span = Span::new(r.span.start, message.span.end);
+                        Expr { kind: ExprKind::Send(Box::new(r), message), span }
+                    }
+                    None => r,
+                }
+            }
+
+        rule keyword_parts() -> Vec<(usize, String, Expr)>
+            = (s:position!() k:keyword() _ a:binary_send() { (s, k, a) }) ++ _
+
+        rule binary_send() -> Expr
+            = r:unary_send() ops:(_ s:position!() o:binary_operator() _ a:unary_send() { (s, o, a) })* {
+                fold_binary(r, ops)
+            }
+
+        rule unary_send() -> Expr
+            = r:primary() ms:(_ s:position!() n:identifier() e:position!() { (s, n, e) })* {
+                fold_unary(r, ms)
+            }
+
+        rule primary() -> Expr
+            = s:position!() l:literal() e:position!() { Expr::new(ExprKind::Literal(l), s, e) }
+            / block()
+            / brace()
+            / "(" _ x:expression() _ ")" { x }
+            / s:position!() n:identifier() e:position!() { Expr::new(ExprKind::Variable(n), s, e) }
+
+        rule block() -> Expr
+            = s:position!() "[" _ p:block_parameters() _ b:body() _ "]" e:position!() {
+                let block = Block { parameters: p, body: b, span: Span::new(s, e) };
+                Expr::new(ExprKind::Block(Arc::new(block)), s, e)
+            }
+
+        rule block_parameters() -> Vec<String>
+            = p:(":" _ n:identifier() _ { n })+ ("|" / &"]") { p }
+            / { vec![] }
+
+        rule brace() -> Expr
+            = s:position!() "{" _ items:(expression() ** (_ "." _)) (_ ".")* _ "}" e:position!() {
+                Expr::new(ExprKind::Brace(items), s, e)
+            }
+
+        rule literal() -> Literal
+            = pseudo_literal()
+            / i:integer() { Literal::Integer(i) }
+            / s:string() { Literal::String(s) }
+            / "#(" _ items:(array_item() ** _) _ ")" { Literal::Array(items) }
+            / "#" s:symbol_body() { Literal::Symbol(s) }
+
+        rule pseudo_literal() -> Literal
+            = "nil" !alphanumeric() { Literal::Nil }
+            / "true" !alphanumeric() { Literal::True }
+            / "false" !alphanumeric() { Literal::False }
+
+        rule array_item() -> Literal
+            = pseudo_literal()
+            / i:integer() { Literal::Integer(i) }
+            / s:string() { Literal::String(s) }
+            / ("#(" / "(") _ items:(array_item() ** _) _ ")" { Literal::Array(items) }
+            / "#"? s:symbol_body() { Literal::Symbol(s) }
+
+        // The range check sits outside `quiet!` so an oversized literal is
+        // reported as such instead of as a missing integer.
+        rule integer() -> i128
+            = n:integer_digits() {? integer(n.0, n.1, n.2) }
+
+        rule integer_digits() -> (Option<&'input str>, &'input str, u32)
+            = quiet!{
+                sign:$("-")? r:$(['0'..='9']+) "r" d:$(['0'..='9' | 'A'..='Z']+) {?
+                    let radix = r.parse::<u32>().map_err(|_| "radix")?;
+                    if !(2..=36).contains(&radix) {
+                        return Err("radix between 2 and 36");
+                    }
+                    Ok((sign, d, radix))
+                }
+                / sign:$("-")? d:$(['0'..='9']+) !("r" ['0'..='9' | 'A'..='Z']) { (sign, d, 10) }
+            }
+            / expected!("integer")
+
+        rule string() -> String
+            = quiet!{ "'" s:$(([^ '\''] / "''")*) "'" { s.replace("''", "'") } }
+            / expected!("string")
+
+        rule symbol_body() -> String
+            = s:$((name() ":")+) { s.to_string() }
+            / n:name() { n.to_string() }
+            / binary_operator()
+            / string()
+
+        rule keyword() -> String
+            = n:name() ":" !"=" { format!("{n}:") }
+
+        rule identifier() -> String
+            = quiet!{ !(pseudo_literal()) n:name() !(":" !"=") { n.to_string() } }
+            / expected!("identifier")
+
+        rule name() -> &'input str
+            = $(['a'..='z' | 'A'..='Z' | '_'] alphanumeric()*)
+
+        rule alphanumeric()
+            = ['a'..='z' | 'A'..='Z' | '0'..='9' | '_']
+
+        rule binary_operator() -> String
+            = quiet!{
+                o:$(['+' | '-' | '*' | '/' | '\\' | '<' | '>' | '=' | '~' | '@' | '%' | '|' | '&' | '?' | ',']+) {
+                    o.to_string()
+                }
+            }
+            / expected!("binary operator")
+
+        rule _()
+            = quiet!{ ([' ' | '\t' | '\r' | '\n'] / comment())* }
+
+        rule comment()
+            = "\"" [^ '"']* "\""
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::sync::Arc;
+
+use peg::str::LineCol;
+
+pub(crate) mod grammar;
+mod tests;
+
+const OUT_OF_RANGE: &str = "integer literal out of range";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub location: LineCol,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+pub fn parse(source: &str) -> Result<Body, ParseError> {
+    grammar::aloxtalk::program(source).map_err(|err| {
+        if !err.expected.tokens().any(|t| t == OUT_OF_RANGE) {
+            return ParseError {
+                location: err.location,
+                message: format!("expected {}", err.expected),
+            };
+        }
+        // The range check fails at the end of the literal; point at its digits.
+        let end = err.location.offset;
+        let start = source[..end].trim_end_matches(|c: char| c.is_ascii_alphanumeric());
+        let (line, column) = Span::new(start.len(), end).line_col(source);
+        ParseError {
+            location: LineCol {
+                line,
+                column,
+                offset: start.len(),
+            },
+            message: OUT_OF_RANGE.to_string(),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+impl Span {
+    pub(crate) fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    pub temporaries: Vec<String>,
+    pub(crate) statements: Vec<Statement>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Statement {
+    Expression(Expr),
+    Return(Expr, Span),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Expr {
+    pub(crate) kind: ExprKind,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ExprKind {
+    Literal(Literal),
+    Variable(String),
+    Move(String),
+    Assign(String, Box<Expr>),
+    Send(Box<Expr>, Message),
+    Cascade(Box<Expr>, Vec<Message>),
+    Block(Arc<Block>),
+    Brace(Vec<Expr>),
+    Class(Arc<ClassDefinition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Message {
+    pub(crate) selector: String,
+    pub(crate) arguments: Vec<Expr>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Literal {
+    Nil,
+    True,
+    False,
+    Integer(i128),
+    String(String),
+    Symbol(String),
+    Array(Vec<Literal>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Block {
+    pub(crate) parameters: Vec<String>,
+    pub(crate) body: Body,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ClassDefinition {
+    pub(crate) name: String,
+    pub(crate) superclass: Option<String>,
+    pub(crate) instance_variables: Vec<String>,
+    pub(crate) methods: Vec<Arc<MethodDefinition>>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MethodDefinition {
+    pub(crate) selector: String,
+    pub(crate) parameters: Vec<String>,
+    pub(crate) body: Body,
+    pub(crate) span: Span,
+}
+
+impl Expr {
+    pub(crate) fn new(kind: ExprKind, start: usize, end: usize) -> Self {
+        Self {
+            kind,
+            span: Span::new(start, end),
+        }
+    }
+}
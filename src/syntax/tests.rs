@@ -0,0 +1,197 @@
+#[cfg(test)]
+use super::*;
+
+#[cfg(test)]
+fn expression(source: &str) -> ExprKind {
+    grammar::aloxtalk::expression(source).unwrap().kind
+}
+
+#[cfg(test)]
+fn selector(kind: &ExprKind) -> &str {
+    match kind {
+        ExprKind::Send(_, message) => &message.selector,
+        _ => panic!("not a send: {kind:?}"),
+    }
+}
+
+#[test]
+fn literals() {
+    assert_eq!(expression("42"), ExprKind::Literal(Literal::Integer(42)));
+    assert_eq!(expression("-7"), ExprKind::Literal(Literal::Integer(-7)));
+    assert_eq!(
+        expression("16rFF"),
+        ExprKind::Literal(Literal::Integer(255))
+    );
+    assert_eq!(
+        expression("170141183460469231731687303715884105727"),
+        ExprKind::Literal(Literal::Integer(i128::MAX))
+    );
+    assert_eq!(
+        expression("'it''s'"),
+        ExprKind::Literal(Literal::String("it's".into()))
+    );
+    assert_eq!(
+        expression("#at:put:"),
+        ExprKind::Literal(Literal::Symbol("at:put:".into()))
+    );
+    assert_eq!(
+        expression("#+"),
+        ExprKind::Literal(Literal::Symbol("+".into()))
+    );
+    assert_eq!(
+        expression("#(1 foo 'bar' #baz (2 nil) true)"),
+        ExprKind::Literal(Literal::Array(vec![
+            Literal::Integer(1),
+            Literal::Symbol("foo".into()),
+            Literal::String("bar".into()),
+            Literal::Symbol("baz".into()),
+            Literal::Array(vec![Literal::Integer(2), Literal::Nil]),
+            Literal::True,
+        ]))
+    );
+    assert_eq!(expression("nil"), ExprKind::Literal(Literal::Nil));
+    assert_eq!(expression("nilly"), ExprKind::Variable("nilly".into()));
+}
+
+#[test]
+fn message_precedence() {
+    let kind = expression("a foo + b bar: c * 2 baz: d");
+    assert_eq!(selector(&kind), "bar:baz:");
+
+    let ExprKind::Send(receiver, message) = kind else {
+        unreachable!()
+    };
+    assert_eq!(selector(&receiver.kind), "+");
+    assert_eq!(selector(&message.arguments[0].kind), "*");
+    assert_eq!(message.arguments[1].kind, ExprKind::Variable("d".into()));
+
+    let ExprKind::Send(receiver, _) = receiver.kind else {
+        unreachable!()
+    };
+    assert_eq!(selector(&receiver.kind), "foo");
+}
+
+#[test]
+fn binary_sends_are_left_associative() {
+    let ExprKind::Send(receiver, message) = expression("1 + 2 * 3") else {
+        panic!()
+    };
+    assert_eq!(message.selector, "*");
+    assert_eq!(selector(&receiver.kind), "+");
+
+    let ExprKind::Send(_, message) = expression("x-1") else {
+        panic!()
+    };
+    assert_eq!(message.selector, "-");
+    assert_eq!(
+        message.arguments[0].kind,
+        ExprKind::Literal(Literal::Integer(1))
+    );
+}
+
+#[test]
+fn cascades() {
+    let ExprKind::Cascade(receiver, messages) =
+        expression("t show: 'a'; cr; + 1; show: 'b' with: 2")
+    else {
+        panic!()
+    };
+    assert_eq!(receiver.kind, ExprKind::Variable("t".into()));
+    let selectors: Vec<_> = messages.iter().map(|m| m.selector.as_str()).collect();
+    assert_eq!(selectors, ["show:", "cr", "+", "show:with:"]);
+}
+
+#[test]
+fn blocks_and_statements() {
+    let body =
+        parse("| a b | a := [:x :y | | t | t := x + y. ^t]. b := a value: 1 value: 2. ^b").unwrap();
+    assert_eq!(body.temporaries, ["a", "b"]);
+    assert_eq!(body.statements.len(), 3);
+    assert!(matches!(body.statements[2], Statement::Return(..)));
+
+    let Statement::Expression(Expr {
+        kind: ExprKind::Assign(name, value),
+        ..
+    }) = &body.statements[0]
+    else {
+        panic!()
+    };
+    assert_eq!(name, "a");
+    let ExprKind::Block(block) = &value.kind else {
+        panic!()
+    };
+    assert_eq!(block.parameters, ["x", "y"]);
+    assert_eq!(block.body.temporaries, ["t"]);
+    assert_eq!(block.body.statements.len(), 2);
+
+    assert_eq!(parse("[]. [:x]. [ ]").unwrap().statements.len(), 3);
+    assert_eq!(parse("").unwrap().statements.len(), 0);
+    assert_eq!(parse(" \"comment\" 1. 2.. ").unwrap().statements.len(), 2);
+}
+
+#[test]
+fn braces() {
+    let ExprKind::Brace(items) = expression("{1. a foo. 'x'.}") else {
+        panic!()
+    };
+    assert_eq!(items.len(), 3);
+}
+
+#[test]
+fn spans() {
+    let source = "x := 3 +\n  foo bar";
+    let body = parse(source).unwrap();
+    let Statement::Expression(assign) = &body.statements[0] else {
+        panic!()
+    };
+    assert_eq!(assign.span, Span::new(0, source.len()));
+
+    let ExprKind::Assign(_, value) = &assign.kind else {
+        panic!()
+    };
+    let ExprKind::Send(receiver, message) = &value.kind else {
+        panic!()
+    };
+    assert_eq!(&source[receiver.span.start..receiver.span.end], "3");
+    assert_eq!(
+        &source[message.span.start..message.span.end],
+        "+\n  foo bar"
+    );
+    assert_eq!(message.arguments[0].span, Span::new(11, 18));
+}
+
+#[test]
+fn parse_errors() {
+    let err = parse("x := (3 + 4").unwrap_err();
+    assert_eq!(err.location.line, 1);
+    assert_eq!(err.location.column, 12);
+    assert!(err.message.starts_with("expected "));
+    assert!(err.message.contains("\")\""));
+
+    let err = parse("a foo.\n  b := ]").unwrap_err();
+    assert_eq!((err.location.line, err.location.column), (2, 8));
+
+    let err = parse("#(1 2").unwrap_err();
+    assert_eq!(err.location.offset, 5);
+
+    assert!(parse("'unterminated").is_err());
+    let err = parse("x := 999999999999999999999999999999999999999999").unwrap_err();
+    assert_eq!(err.message, "integer literal out of range");
+    assert_eq!((err.location.offset, err.location.column), (5, 6));
+    let err = parse("#(1 -36rZZZZZZZZZZZZZZZZZZZZZZZZZZZZ)").unwrap_err();
+    assert_eq!(err.message, "integer literal out of range");
+    assert!(parse("x := -170141183460469231731687303715884105728").is_ok());
+    assert!(parse("40rZZ").is_err());
+    assert!(parse("a b: c d:").is_err());
+}
+
+#[test]
+fn span_line_col() {
+    let source = "a := 1.\nb := 2.\n  c foo";
+    let body = parse(source).unwrap();
+    let Statement::Expression(expr) = &body.statements[2] else {
+        panic!("expected an expression");
+    };
+    assert_eq!(expr.span.line_col(source), (3, 3));
+    assert_eq!(Span::new(0, 1).line_col(source), (1, 1));
+}